@@ -1,29 +1,72 @@
+//! Finds `libxdp` and `libbpf`, then generates the bindings.
+//!
+//! The libraries are looked up in this order:
+//!
+//! 1. With the `vendored` feature, they are built from the `xdp-tools`
+//!    submodule and linked statically.
+//! 2. `LIBXDP_LIB_DIR`, and optionally `LIBXDP_INCLUDE_DIR`, point at the
+//!    directories with the libraries and the headers.
+//! 3. `pkg-config`, which honors `PKG_CONFIG_PATH` and
+//!    `PKG_CONFIG_SYSROOT_DIR` for cross compiling.
+//! 4. The default library paths from `/etc/ld.so.conf`, and the include
+//!    paths of the host compiler.
+//!
+//! The libraries are linked dynamically unless `LIBXDP_STATIC` is `1`,
+//! `true` or `yes`. `0`, `false` and `no` keep dynamic linking, and any
+//! other value fails the build.
+
 use bindgen::Builder;
 use mangonel_util::system;
-use std::{env, path::PathBuf};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 const WRAPPER: &str = "wrapper.h";
+const SUBMODULE: &str = "../../xdp-tools";
+/// Libraries that a static `libbpf` needs when `pkg-config` does not say.
+const STATIC_DEPENDENCIES: [&str; 2] = ["elf", "z"];
 
 fn main() {
-    system::check_system_info().unwrap();
-    let default_lib_paths = system::default_library_paths().unwrap();
-
-    // Tell cargo where to find the static libraries
-    for lib_path in default_lib_paths {
-        println!("cargo::rustc-link-search=native={}", lib_path.display());
-    }
-    println!("cargo::rustc-link-lib=static=bpf");
-    println!("cargo::rustc-link-lib=static=xdp");
     println!("cargo::rerun-if-changed={WRAPPER}");
+    for name in ["LIBXDP_LIB_DIR", "LIBXDP_INCLUDE_DIR", "LIBXDP_STATIC"] {
+        println!("cargo::rerun-if-env-changed={name}");
+    }
 
-    let default_include_paths = system::default_include_paths().unwrap();
+    let mut include_paths: Vec<PathBuf> = env::var_os("LIBXDP_INCLUDE_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    if env::var_os("CARGO_FEATURE_VENDORED").is_some() {
+        include_paths.extend(build_vendored());
+    } else if let Some(lib_dir) = env::var_os("LIBXDP_LIB_DIR") {
+        link(
+            &[PathBuf::from(lib_dir)],
+            &STATIC_DEPENDENCIES.map(String::from),
+        );
+    } else {
+        match probe_pkg_config() {
+            Ok(paths) => include_paths.extend(paths),
+            Err(_) => {
+                println!("cargo::warning=pkg-config did not find libxdp, using the default paths");
+                link(
+                    &system::default_library_paths().unwrap(),
+                    &STATIC_DEPENDENCIES.map(String::from),
+                );
+                // The host headers are only a guess, so they come last. `cpp`
+                // is missing on some systems, e.g. Nix shells with only clang.
+                include_paths.extend(system::default_include_paths().unwrap_or_default());
+            }
+        }
+    }
 
     // Generate bindings with clang include paths
     let mut builder = Builder::default()
         .header(WRAPPER)
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate_inline_functions(true);
-    for include_path in default_include_paths {
+    for include_path in include_paths {
         builder = builder.clang_arg(format!("-I{}", include_path.display()));
     }
     let bindings = builder.generate().expect("Unable to generate bindings");
@@ -33,3 +76,161 @@ fn main() {
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Failed to write bindings");
 }
+
+/// Whether `LIBXDP_STATIC` asks for static linking.
+fn link_static() -> bool {
+    let Some(value) = env::var_os("LIBXDP_STATIC") else {
+        return false;
+    };
+    match value.to_str() {
+        Some("1" | "true" | "yes") => true,
+        Some("0" | "false" | "no") => false,
+        _ => panic!("LIBXDP_STATIC must be 1, true, yes, 0, false or no, not {value:?}"),
+    }
+}
+
+/// Links `libxdp` and `libbpf` from `lib_dirs`. `private_libs` are the
+/// libraries that a static `libbpf` needs, such as `elf` and `z`.
+fn link(lib_dirs: &[PathBuf], private_libs: &[String]) {
+    let link_static = link_static();
+    let mut lib_dirs = lib_dirs.to_vec();
+    // `pkg-config` leaves out `-L` for the system directories. rustc needs
+    // the directory to find a static archive, so look for it there.
+    if link_static && lib_dirs.is_empty() {
+        lib_dirs.extend(
+            system::default_library_paths()
+                .unwrap_or_default()
+                .into_iter()
+                .find(|dir| dir.join("libxdp.a").is_file()),
+        );
+    }
+    for lib_dir in &lib_dirs {
+        println!("cargo::rustc-link-search=native={}", lib_dir.display());
+    }
+
+    let kind = match link_static {
+        true => "static",
+        false => "dylib",
+    };
+    // `libxdp` uses `libbpf`, so it comes first for static linking.
+    println!("cargo::rustc-link-lib={kind}=xdp");
+    println!("cargo::rustc-link-lib={kind}=bpf");
+    if kind == "static" {
+        for lib in private_libs {
+            println!("cargo::rustc-link-lib={lib}");
+        }
+    }
+}
+
+/// Finds the libraries with `pkg-config` and returns the include paths.
+fn probe_pkg_config() -> Result<Vec<PathBuf>, pkg_config::Error> {
+    let mut lib_dirs = Vec::new();
+    let mut include_paths = Vec::new();
+    let mut private_libs = Vec::new();
+    for name in ["libxdp", "libbpf"] {
+        // The static flags also list the private dependencies. The link flags
+        // are printed by `link()`, but the variables that pkg-config reads,
+        // such as `PKG_CONFIG_PATH`, still have to trigger a rebuild.
+        let library = pkg_config::Config::new()
+            .statik(true)
+            .cargo_metadata(false)
+            .env_metadata(true)
+            .probe(name)?;
+        for path in library.link_paths {
+            if !lib_dirs.contains(&path) {
+                lib_dirs.push(path);
+            }
+        }
+        include_paths.extend(library.include_paths);
+        for lib in library.libs {
+            if lib != "xdp" && lib != "bpf" && !private_libs.contains(&lib) {
+                private_libs.push(lib);
+            }
+        }
+    }
+
+    link(&lib_dirs, &private_libs);
+    Ok(include_paths)
+}
+
+/// Builds `libbpf` and `libxdp` from the submodule in `OUT_DIR` and returns
+/// the include path.
+fn build_vendored() -> Vec<PathBuf> {
+    let source = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(SUBMODULE);
+    if !source.join("lib/libbpf/src/Makefile").is_file() {
+        panic!(
+            "The xdp-tools submodule is missing at {}. Run `git submodule update --init --recursive`",
+            source.display()
+        );
+    }
+    println!("cargo::rerun-if-changed={}", source.display());
+
+    // The build writes into the source tree, so it runs on a copy.
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let build_dir = out_dir.join("xdp-tools");
+    if build_dir.exists() {
+        fs::remove_dir_all(&build_dir).unwrap();
+    }
+    copy_dir(&source, &build_dir).unwrap();
+
+    let jobs = format!(
+        "-j{}",
+        env::var("NUM_JOBS").unwrap_or_else(|_| "1".to_owned())
+    );
+    run(Command::new("./configure").current_dir(&build_dir));
+    run(Command::new("make")
+        .args([&jobs, "libxdp"])
+        .current_dir(&build_dir));
+
+    let include_dir = out_dir.join("include");
+    run(Command::new("make")
+        .args(["-C", "lib/libbpf/src", "install_headers", "INCLUDEDIR=/"])
+        .arg(format!("DESTDIR={}", include_dir.display()))
+        .current_dir(&build_dir));
+    copy_dir(&build_dir.join("headers/xdp"), &include_dir.join("xdp")).unwrap();
+
+    println!(
+        "cargo::rustc-link-search=native={}",
+        build_dir.join("lib/libxdp").display()
+    );
+    println!(
+        "cargo::rustc-link-search=native={}",
+        build_dir.join("lib/libbpf/src").display()
+    );
+    println!("cargo::rustc-link-lib=static=xdp");
+    println!("cargo::rustc-link-lib=static=bpf");
+    for lib in STATIC_DEPENDENCIES {
+        println!("cargo::rustc-link-lib={lib}");
+    }
+
+    vec![include_dir]
+}
+
+fn copy_dir(source: &Path, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = destination.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &path)?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &path)?;
+        } else {
+            fs::copy(entry.path(), &path)?;
+        }
+    }
+    Ok(())
+}
+
+fn run(command: &mut Command) {
+    let status = command
+        .status()
+        .unwrap_or_else(|error| panic!("Failed to run {command:?}: {error}"));
+    if !status.success() {
+        panic!("{command:?} failed with {status}");
+    }
+}
@@ -1,2 +1,3 @@
 #include <xdp/xsk.h>
-#include <xdp/libxdp.h>
\ No newline at end of file
+#include <xdp/libxdp.h>
+#include <bpf/bpf.h>
\ No newline at end of file
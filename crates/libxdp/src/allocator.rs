@@ -0,0 +1,389 @@
+use crate::umem::FrameClaim;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// How the frames claimed by a socket pair are divided between [`TxSocket`]
+/// and [`RxSocket`].
+///
+/// [`TxSocket`]: crate::TxSocket
+/// [`RxSocket`]: crate::RxSocket
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameAllocation {
+    /// Both sockets draw from one lock-free pool. Frames completed by TX are
+    /// used to refill RX, which suits forwarding.
+    #[default]
+    Shared,
+    /// Each socket gets a pool of its own, so the pools are never contended.
+    /// `tx_frame_count` frames go to TX and the rest go to RX, which suits
+    /// pure generators and pure receivers. Frames must not be passed from
+    /// one socket to the other in this mode.
+    ///
+    /// The pools are owned by their socket alone and need no
+    /// synchronization, so [`Frame`]s, which keep a handle to their pool,
+    /// are not available.
+    ///
+    /// [`Frame`]: crate::Frame
+    Split { tx_frame_count: u32 },
+}
+
+/// Free list of UMEM frames.
+///
+/// Frames are identified by the address of their first byte. Any address
+/// inside a frame can be freed; it is normalized back to the start of the
+/// frame.
+#[derive(Debug)]
+pub struct FrameAllocator {
+    free_list: FreeList,
+    frame_count: u32,
+    frame_stride: u64,
+    /// Every frame of the pool, sorted, to tell foreign addresses apart.
+    frames: Arc<[u64]>,
+    /// Keeps the frames out of the [`Umem`] while any handle is alive.
+    ///
+    /// [`Umem`]: crate::Umem
+    claim: Option<Arc<FrameClaim>>,
+}
+
+#[derive(Debug)]
+enum FreeList {
+    Local(Vec<u64>),
+    Shared(Arc<SharedFreeList>),
+}
+
+impl FrameAllocator {
+    /// Creates an allocator that is owned by a single socket.
+    pub fn new(frames: Vec<u64>, frame_stride: u64) -> Self {
+        Self {
+            frame_count: frames.len() as u32,
+            frames: sorted(&frames),
+            free_list: FreeList::Local(frames),
+            frame_stride,
+            claim: None,
+        }
+    }
+
+    /// Creates an allocator that can be handed to several sockets with
+    /// [`FrameAllocator::share`].
+    pub fn new_shared(frames: Vec<u64>, frame_stride: u64) -> Self {
+        let free_list = SharedFreeList::new(frames.len());
+        for address in &frames {
+            free_list.push(*address).unwrap();
+        }
+
+        Self {
+            free_list: FreeList::Shared(free_list.into()),
+            frame_count: frames.len() as u32,
+            frames: sorted(&frames),
+            frame_stride,
+            claim: None,
+        }
+    }
+
+    /// Ties the frames of `claim` to this allocator and the handles shared
+    /// from it.
+    pub(crate) fn with_claim(mut self, claim: Arc<FrameClaim>) -> Self {
+        self.claim = Some(claim);
+        self
+    }
+
+    /// Returns another handle to the same pool, or `None` for a single-owner
+    /// allocator.
+    pub fn share(&self) -> Option<Self> {
+        match &self.free_list {
+            FreeList::Local(_) => None,
+            FreeList::Shared(free_list) => Some(Self {
+                free_list: FreeList::Shared(free_list.clone()),
+                frame_count: self.frame_count,
+                frame_stride: self.frame_stride,
+                frames: self.frames.clone(),
+                claim: self.claim.clone(),
+            }),
+        }
+    }
+
+    /// Whether the pool can be handed to other sockets and to [`Frame`]s.
+    ///
+    /// [`Frame`]: crate::Frame
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        matches!(self.free_list, FreeList::Shared(_))
+    }
+
+    /// Takes a free frame out of the pool.
+    #[inline]
+    pub fn allocate(&mut self) -> Option<u64> {
+        match &mut self.free_list {
+            FreeList::Local(frames) => frames.pop(),
+            FreeList::Shared(free_list) => free_list.pop(),
+        }
+    }
+
+    /// Puts a frame back into the pool.
+    ///
+    /// # Panics
+    ///
+    /// The function panics when [`FrameAllocator::try_free`] fails.
+    #[inline]
+    pub fn free(&mut self, address: u64) {
+        if let Err(error) = self.try_free(address) {
+            panic!("{error}");
+        }
+    }
+
+    /// Puts a frame back into the pool, unless the frame is not one of the
+    /// pool or the pool is already full, which means that a frame was freed
+    /// twice.
+    #[inline]
+    pub fn try_free(&mut self, address: u64) -> Result<(), FreeError> {
+        let address = address - address % self.frame_stride;
+        if !self.owns(address) {
+            return Err(FreeError::ForeignFrame(address));
+        }
+        match &mut self.free_list {
+            FreeList::Local(frames) => {
+                if frames.len() as u32 == self.frame_count {
+                    return Err(FreeError::Overflow);
+                }
+                frames.push(address);
+            }
+            FreeList::Shared(free_list) => {
+                free_list.push(address).map_err(|_| FreeError::Overflow)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the frame at `address` belongs to this pool.
+    #[inline]
+    pub fn owns(&self, address: u64) -> bool {
+        let address = address - address % self.frame_stride;
+        self.frames.binary_search(&address).is_ok()
+    }
+
+    /// Number of frames managed by this pool.
+    #[inline]
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Number of frames currently in the pool.
+    ///
+    /// For a shared pool this is a snapshot that may already be stale.
+    #[inline]
+    pub fn free_count(&self) -> u32 {
+        match &self.free_list {
+            FreeList::Local(frames) => frames.len() as u32,
+            FreeList::Shared(free_list) => free_list.len() as u32,
+        }
+    }
+
+    /// Number of frames handed out and not yet freed, i.e. frames owned by
+    /// the kernel rings or by the application.
+    #[inline]
+    pub fn in_flight_count(&self) -> u32 {
+        self.frame_count - self.free_count()
+    }
+}
+
+fn sorted(frames: &[u64]) -> Arc<[u64]> {
+    let mut frames = frames.to_vec();
+    frames.sort_unstable();
+    frames.into()
+}
+
+/// Bounded lock-free MPMC queue of frame addresses.
+///
+/// Each slot carries a sequence number that tells producers and consumers
+/// whether the slot is ready for them, so no locks are needed. `length`
+/// counts pushes that have started minus pops that have finished, so it
+/// never drops below the true number of frames in the queue and reaching
+/// `frame_count` means a frame was freed twice.
+#[derive(Debug)]
+struct SharedFreeList {
+    slots: Box<[Slot]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    length: AtomicUsize,
+    frame_count: usize,
+}
+
+#[derive(Debug)]
+struct Slot {
+    sequence: AtomicUsize,
+    address: AtomicU64,
+}
+
+impl SharedFreeList {
+    fn new(frame_count: usize) -> Self {
+        let capacity = frame_count.max(1).next_power_of_two();
+        let slots = (0..capacity)
+            .map(|index| Slot {
+                sequence: AtomicUsize::new(index),
+                address: AtomicU64::new(0),
+            })
+            .collect();
+
+        Self {
+            slots,
+            mask: capacity - 1,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            length: AtomicUsize::new(0),
+            frame_count,
+        }
+    }
+
+    fn push(&self, address: u64) -> Result<(), u64> {
+        if self.length.fetch_add(1, Ordering::AcqRel) >= self.frame_count {
+            self.length.fetch_sub(1, Ordering::AcqRel);
+            return Err(address);
+        }
+
+        let mut position = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let difference = sequence as isize - position as isize;
+            if difference == 0 {
+                match self.tail.compare_exchange_weak(
+                    position,
+                    position + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        slot.address.store(address, Ordering::Relaxed);
+                        slot.sequence.store(position + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => position = current,
+                }
+            } else if difference < 0 {
+                // The queue cannot hold more than `frame_count` frames, so the
+                // slot is still being read by a pop that has not finished.
+                std::hint::spin_loop();
+                position = self.tail.load(Ordering::Relaxed);
+            } else {
+                position = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<u64> {
+        let mut position = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let difference = sequence as isize - (position + 1) as isize;
+            if difference == 0 {
+                match self.head.compare_exchange_weak(
+                    position,
+                    position + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let address = slot.address.load(Ordering::Relaxed);
+                        self.length.fetch_sub(1, Ordering::AcqRel);
+                        slot.sequence
+                            .store(position + self.mask + 1, Ordering::Release);
+                        return Some(address);
+                    }
+                    Err(current) => position = current,
+                }
+            } else if difference < 0 {
+                return None;
+            } else {
+                position = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.length.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum FreeError {
+    #[error("Frame {0:#x} does not belong to this pool")]
+    ForeignFrame(u64),
+    #[error("Frame allocator overflow. A frame was freed twice.")]
+    Overflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn frames(count: u64) -> Vec<u64> {
+        (0..count).map(|index| index * 4096).collect()
+    }
+
+    #[test]
+    fn local_allocate_and_free() {
+        let mut allocator = FrameAllocator::new(frames(4), 4096);
+        assert!(allocator.share().is_none());
+        assert!(!allocator.is_shared());
+
+        let address = allocator.allocate().unwrap();
+        assert_eq!(allocator.free_count(), 3);
+        assert_eq!(allocator.in_flight_count(), 1);
+
+        // Addresses inside the frame are normalized.
+        allocator.free(address + 256);
+        assert_eq!(allocator.free_count(), 4);
+        assert_eq!(allocator.allocate(), Some(address));
+    }
+
+    #[test]
+    #[should_panic]
+    fn local_double_free() {
+        let mut allocator = FrameAllocator::new(frames(1), 4096);
+        allocator.free(0);
+    }
+
+    #[test]
+    fn foreign_frame() {
+        let mut allocator = FrameAllocator::new(frames(2), 4096);
+        allocator.allocate().unwrap();
+        assert!(allocator.owns(4096 + 256));
+        assert!(!allocator.owns(2 * 4096));
+        assert_eq!(
+            allocator.try_free(2 * 4096 + 256),
+            Err(FreeError::ForeignFrame(2 * 4096))
+        );
+        assert_eq!(allocator.try_free(0), Ok(()));
+        assert_eq!(allocator.try_free(0), Err(FreeError::Overflow));
+    }
+
+    #[test]
+    fn shared_across_threads() {
+        let mut allocator = FrameAllocator::new_shared(frames(1024), 4096);
+        assert!(allocator.is_shared());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mut allocator = allocator.share().unwrap();
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        if let Some(address) = allocator.allocate() {
+                            allocator.free(address);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut addresses: Vec<u64> = std::iter::from_fn(|| allocator.allocate()).collect();
+        addresses.sort_unstable();
+        assert_eq!(addresses, frames(1024));
+        assert_eq!(allocator.in_flight_count(), 1024);
+    }
+}
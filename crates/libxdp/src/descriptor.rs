@@ -5,6 +5,12 @@ pub struct Descriptor {
     pub address: u64,
     pub length: u32,
     pub drop: bool,
+    /// `XDP_PKT_CONTD`: the packet continues in the next descriptor. Only
+    /// sockets built with [`SocketBuilder::use_multi_buffer`] chain
+    /// descriptors.
+    ///
+    /// [`SocketBuilder::use_multi_buffer`]: crate::SocketBuilder::use_multi_buffer
+    pub continued: bool,
 }
 
 impl Descriptor {
@@ -18,8 +24,13 @@ impl Descriptor {
         unsafe { std::slice::from_raw_parts(offset, length as usize) }
     }
 
+    /// The slice borrows the descriptor mutably, but clones of the descriptor
+    /// still point at the same frame. Prefer [`Frame`] when the frame must
+    /// not be aliased.
+    ///
+    /// [`Frame`]: crate::Frame
     #[inline]
-    pub fn as_slice_mut<'a>(&mut self, umem: &'a Umem) -> &'a mut [u8] {
+    pub fn as_slice_mut<'a>(&'a mut self, umem: &'a Umem) -> &'a mut [u8] {
         let headroom_size = umem.config().frame_headroom;
         let address = self.address - headroom_size as u64;
         let length = self.length as u64 + headroom_size as u64;
@@ -27,4 +38,167 @@ impl Descriptor {
 
         unsafe { std::slice::from_raw_parts_mut(offset, length as usize) }
     }
+
+    /// Number of free bytes between the start of the frame and the packet.
+    #[inline]
+    pub fn headroom(&self, umem: &Umem) -> u32 {
+        self.headroom_in(umem.frame_stride())
+    }
+
+    /// Number of free bytes between the end of the packet and the end of the
+    /// frame.
+    #[inline]
+    pub fn tailroom(&self, umem: &Umem) -> u32 {
+        self.tailroom_in(umem.frame_stride())
+    }
+
+    /// Grows the packet by `size` bytes at the front, into the headroom. The
+    /// new bytes are left as they were in the frame.
+    #[inline]
+    pub fn push_front(&mut self, umem: &Umem, size: u32) -> Result<(), FrameError> {
+        self.push_front_in(umem.frame_stride(), size)
+    }
+
+    /// Shrinks the packet by `size` bytes at the front, giving them back to
+    /// the headroom.
+    #[inline]
+    pub fn pull_front(&mut self, size: u32) -> Result<(), FrameError> {
+        self.check_length(size)?;
+        self.address += size as u64;
+        self.length -= size;
+        Ok(())
+    }
+
+    /// Grows the packet by `size` bytes at the back, into the tailroom. The
+    /// new bytes are left as they were in the frame.
+    #[inline]
+    pub fn push_back(&mut self, umem: &Umem, size: u32) -> Result<(), FrameError> {
+        self.push_back_in(umem.frame_stride(), size)
+    }
+
+    /// Shrinks the packet by `size` bytes at the back.
+    #[inline]
+    pub fn trim_back(&mut self, size: u32) -> Result<(), FrameError> {
+        self.check_length(size)?;
+        self.length -= size;
+        Ok(())
+    }
+
+    #[inline]
+    fn headroom_in(&self, frame_stride: u64) -> u32 {
+        (self.address % frame_stride) as u32
+    }
+
+    #[inline]
+    fn tailroom_in(&self, frame_stride: u64) -> u32 {
+        (frame_stride - self.address % frame_stride) as u32 - self.length
+    }
+
+    #[inline]
+    fn push_front_in(&mut self, frame_stride: u64, size: u32) -> Result<(), FrameError> {
+        let available = self.headroom_in(frame_stride);
+        if size > available {
+            return Err(FrameError::InsufficientHeadroom {
+                requested: size,
+                available,
+            });
+        }
+        self.address -= size as u64;
+        self.length += size;
+        Ok(())
+    }
+
+    #[inline]
+    fn push_back_in(&mut self, frame_stride: u64, size: u32) -> Result<(), FrameError> {
+        let available = self.tailroom_in(frame_stride);
+        if size > available {
+            return Err(FrameError::InsufficientTailroom {
+                requested: size,
+                available,
+            });
+        }
+        self.length += size;
+        Ok(())
+    }
+
+    #[inline]
+    fn check_length(&self, size: u32) -> Result<(), FrameError> {
+        if size > self.length {
+            return Err(FrameError::ExceedsLength {
+                requested: size,
+                length: self.length,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum FrameError {
+    #[error("Requested {requested} bytes of headroom but only {available} are left")]
+    InsufficientHeadroom { requested: u32, available: u32 },
+    #[error("Requested {requested} bytes of tailroom but only {available} are left")]
+    InsufficientTailroom { requested: u32, available: u32 },
+    #[error("Cannot remove {requested} bytes from a {length} byte packet")]
+    ExceedsLength { requested: u32, length: u32 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_STRIDE: u64 = 2048;
+
+    #[test]
+    fn push_and_pull_front() {
+        let mut descriptor = Descriptor {
+            address: FRAME_STRIDE + 256,
+            length: 100,
+            drop: false,
+            continued: false,
+        };
+        assert_eq!(descriptor.headroom_in(FRAME_STRIDE), 256);
+
+        descriptor.push_front_in(FRAME_STRIDE, 14).unwrap();
+        assert_eq!(descriptor.address, FRAME_STRIDE + 242);
+        assert_eq!(descriptor.length, 114);
+
+        assert_eq!(
+            descriptor.push_front_in(FRAME_STRIDE, 243),
+            Err(FrameError::InsufficientHeadroom {
+                requested: 243,
+                available: 242
+            })
+        );
+
+        descriptor.pull_front(14).unwrap();
+        assert_eq!(descriptor.address, FRAME_STRIDE + 256);
+        assert_eq!(descriptor.length, 100);
+        assert!(descriptor.pull_front(101).is_err());
+    }
+
+    #[test]
+    fn push_and_trim_back() {
+        let mut descriptor = Descriptor {
+            address: 256,
+            length: 100,
+            drop: false,
+            continued: false,
+        };
+        assert_eq!(descriptor.tailroom_in(FRAME_STRIDE), 1692);
+
+        descriptor.push_back_in(FRAME_STRIDE, 1692).unwrap();
+        assert_eq!(descriptor.tailroom_in(FRAME_STRIDE), 0);
+        assert!(descriptor.push_back_in(FRAME_STRIDE, 1).is_err());
+
+        descriptor.trim_back(1792).unwrap();
+        assert_eq!(descriptor.length, 0);
+        assert_eq!(
+            descriptor.trim_back(1),
+            Err(FrameError::ExceedsLength {
+                requested: 1,
+                length: 0
+            })
+        );
+    }
 }
@@ -0,0 +1,173 @@
+use crate::{
+    allocator::FrameAllocator,
+    descriptor::{Descriptor, FrameError},
+    umem::Umem,
+};
+use std::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+
+/// An owned UMEM frame holding one packet.
+///
+/// A `Frame` is the only handle to its frame, so the packet bytes can be
+/// borrowed mutably without aliasing. It borrows the [`Umem`] so that it
+/// cannot outlive the memory map, and it goes back to the pool it was
+/// allocated from when dropped. The pool keeps the frames of its socket
+/// claimed until the last `Frame` is gone, so other sockets sharing the
+/// [`Umem`] cannot get them after the socket is dropped. Passing it to
+/// [`TxSocket::write_frames`] hands it to the kernel instead.
+///
+/// [`TxSocket::write_frames`]: crate::TxSocket::write_frames
+pub struct Frame<'a> {
+    descriptor: Descriptor,
+    umem: &'a Umem,
+    frame_allocator: ManuallyDrop<FrameAllocator>,
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.frame_allocator.free(self.descriptor.address);
+        unsafe { ManuallyDrop::drop(&mut self.frame_allocator) };
+    }
+}
+
+impl fmt::Debug for Frame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field("address", &self.descriptor.address)
+            .field("length", &self.descriptor.length)
+            .finish()
+    }
+}
+
+impl Deref for Frame<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        let offset = self.umem.get_data(self.descriptor.address) as *const u8;
+        unsafe { std::slice::from_raw_parts(offset, self.descriptor.length as usize) }
+    }
+}
+
+impl DerefMut for Frame<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let offset = self.umem.get_data(self.descriptor.address) as *mut u8;
+        unsafe { std::slice::from_raw_parts_mut(offset, self.descriptor.length as usize) }
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// Wraps a descriptor whose frame is exclusively owned by the caller.
+    /// `frame_allocator` must be a handle to the pool the frame came from.
+    #[inline]
+    pub(crate) fn new(
+        descriptor: Descriptor,
+        umem: &'a Umem,
+        frame_allocator: FrameAllocator,
+    ) -> Self {
+        Self {
+            descriptor,
+            umem,
+            frame_allocator: ManuallyDrop::new(frame_allocator),
+        }
+    }
+
+    /// Gives up ownership without returning the frame to the pool. Used when
+    /// the frame is handed to the kernel.
+    #[inline]
+    pub(crate) fn into_descriptor(self) -> Descriptor {
+        let mut frame = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::drop(&mut frame.frame_allocator) };
+        frame.descriptor.clone()
+    }
+
+    #[inline]
+    pub fn descriptor(&self) -> &Descriptor {
+        &self.descriptor
+    }
+
+    #[inline]
+    pub fn umem(&self) -> &'a Umem {
+        self.umem
+    }
+
+    /// Number of bytes between the start of the packet and the end of the
+    /// frame.
+    #[inline]
+    pub fn capacity(&self) -> u32 {
+        self.descriptor.length + self.tailroom()
+    }
+
+    /// See [`Descriptor::headroom`].
+    #[inline]
+    pub fn headroom(&self) -> u32 {
+        self.descriptor.headroom(self.umem)
+    }
+
+    /// See [`Descriptor::tailroom`].
+    #[inline]
+    pub fn tailroom(&self) -> u32 {
+        self.descriptor.tailroom(self.umem)
+    }
+
+    /// Prepends `size` bytes taken from the headroom, e.g. to add an outer
+    /// header, and returns them for writing.
+    #[inline]
+    pub fn push_front(&mut self, size: u32) -> Result<&mut [u8], FrameError> {
+        self.descriptor.push_front(self.umem, size)?;
+        Ok(&mut self[..size as usize])
+    }
+
+    /// Strips `size` bytes off the front, e.g. to remove an outer header.
+    #[inline]
+    pub fn pull_front(&mut self, size: u32) -> Result<(), FrameError> {
+        self.descriptor.pull_front(size)
+    }
+
+    /// Appends `size` bytes taken from the tailroom and returns them for
+    /// writing.
+    #[inline]
+    pub fn push_back(&mut self, size: u32) -> Result<&mut [u8], FrameError> {
+        self.descriptor.push_back(self.umem, size)?;
+        let length = self.descriptor.length as usize;
+        Ok(&mut self[length - size as usize..])
+    }
+
+    /// Strips `size` bytes off the back, e.g. to remove a trailer.
+    #[inline]
+    pub fn trim_back(&mut self, size: u32) -> Result<(), FrameError> {
+        self.descriptor.trim_back(size)
+    }
+
+    /// Whether the packet continues in the next frame. See
+    /// [`Descriptor::continued`].
+    #[inline]
+    pub fn is_continued(&self) -> bool {
+        self.descriptor.continued
+    }
+
+    /// Marks the packet as continuing in the next frame that is sent.
+    #[inline]
+    pub fn set_continued(&mut self, continued: bool) {
+        self.descriptor.continued = continued;
+    }
+
+    /// Sets the packet length.
+    ///
+    /// # Panics
+    ///
+    /// The function panics when `length` exceeds [`Frame::capacity`].
+    #[inline]
+    pub fn set_len(&mut self, length: u32) {
+        assert!(
+            length <= self.capacity(),
+            "Frame length {length} exceeds the capacity {}",
+            self.capacity()
+        );
+        self.descriptor.length = length;
+    }
+}
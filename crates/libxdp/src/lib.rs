@@ -1,9 +1,26 @@
+mod allocator;
 mod descriptor;
+mod frame;
 mod mmap;
+mod packet;
+mod program;
 mod ring;
 mod socket;
+mod stats;
 mod umem;
 mod util;
 
-pub use descriptor::Descriptor;
-pub use socket::{RxSocket, Socket, SocketBuilder, SocketError, TxSocket};
+pub use allocator::{FrameAllocation, FrameAllocator, FreeError};
+pub use descriptor::{Descriptor, FrameError};
+pub use frame::Frame;
+pub use mangonel_util::capability::{
+    Capabilities, InterfaceCapabilities, ProbeError, ProbeMode, Support,
+};
+pub use mmap::{MmapError, PageSize};
+pub use packet::{Packet, Packets};
+pub use program::{AttachMode, XdpProgram, XdpProgramError, XskMap};
+pub use socket::{
+    BindMode, ReadError, RxSocket, Socket, SocketBuilder, SocketError, TxOutcome, TxSocket,
+};
+pub use stats::{SocketCounters, StatsError, XdpStatistics};
+pub use umem::{Umem, UmemError};
@@ -1,11 +1,80 @@
 use libc::{
-    MAP_ANONYMOUS, MAP_FAILED, MAP_HUGETLB, MAP_PRIVATE, PROT_READ, PROT_WRITE, mmap, munmap,
+    MAP_ANONYMOUS, MAP_FAILED, MAP_HUGE_1GB, MAP_HUGE_2MB, MAP_HUGETLB, MAP_PRIVATE, PROT_READ,
+    PROT_WRITE, mmap, munmap,
+};
+use mangonel_util::{
+    capability::{HugepagePool, ProbeError},
+    numa,
 };
 use std::{
     ffi::c_void,
+    fmt,
     ptr::{NonNull, null_mut},
 };
 
+/// Size of the pages that back the UMEM.
+///
+/// Hugepages need fewer TLB entries for the same UMEM. They have to be
+/// reserved up front, e.g. in
+/// `/sys/kernel/mm/hugepages/hugepages-2048kB/nr_hugepages`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageSize {
+    /// The base page size of the system, usually 4 KiB.
+    #[default]
+    Normal,
+    /// 2 MiB hugepages (`MAP_HUGE_2MB`).
+    Huge2MiB,
+    /// 1 GiB hugepages (`MAP_HUGE_1GB`).
+    Huge1GiB,
+}
+
+impl PageSize {
+    /// Page size in bytes.
+    #[inline]
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::Normal => unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize },
+            Self::Huge2MiB => 2 << 20,
+            Self::Huge1GiB => 1 << 30,
+        }
+    }
+
+    #[inline]
+    pub fn is_huge(self) -> bool {
+        self != Self::Normal
+    }
+
+    /// Number of pages that hold `length` bytes.
+    #[inline]
+    pub fn pages(self, length: usize) -> usize {
+        length.div_ceil(self.bytes())
+    }
+
+    /// Rounds `length` up to a multiple of the page size.
+    #[inline]
+    pub fn round_up(self, length: usize) -> usize {
+        self.pages(length) * self.bytes()
+    }
+
+    fn flags(self) -> i32 {
+        match self {
+            Self::Normal => 0,
+            Self::Huge2MiB => MAP_HUGETLB | MAP_HUGE_2MB,
+            Self::Huge1GiB => MAP_HUGETLB | MAP_HUGE_1GB,
+        }
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Normal => write!(f, "{} KiB", self.bytes() / 1024),
+            Self::Huge2MiB => write!(f, "2 MiB"),
+            Self::Huge1GiB => write!(f, "1 GiB"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Mmap {
     address: NonNull<c_void>,
@@ -33,22 +102,49 @@ impl Drop for Mmap {
 }
 
 impl Mmap {
-    pub fn new(length: usize, hugetlb: bool) -> Result<Self, MmapError> {
-        let protection_mode = PROT_READ | PROT_WRITE;
-        let mut flags = MAP_PRIVATE | MAP_ANONYMOUS;
-        if hugetlb {
-            flags |= MAP_HUGETLB;
+    /// Maps at least `length` bytes of anonymous memory, rounded up to a
+    /// multiple of `page_size`. With `numa_node`, the pages are only
+    /// allocated on that NUMA node.
+    ///
+    /// For hugepages, the pool in `/sys/kernel/mm/hugepages`, or the pool of
+    /// `numa_node`, is checked first so that a missing reservation is
+    /// reported instead of `ENOMEM`.
+    pub fn new(
+        length: usize,
+        page_size: PageSize,
+        numa_node: Option<u32>,
+    ) -> Result<Self, MmapError> {
+        let length = page_size.round_up(length);
+        if page_size.is_huge() {
+            let pool =
+                HugepagePool::read(page_size.bytes(), numa_node).map_err(MmapError::Hugepages)?;
+            check_hugepages(page_size, page_size.pages(length) as u64, pool)?;
         }
 
+        let protection_mode = PROT_READ | PROT_WRITE;
+        let flags = MAP_PRIVATE | MAP_ANONYMOUS | page_size.flags();
+
         let address = unsafe { mmap(null_mut(), length, protection_mode, flags, -1, 0) };
         if address == MAP_FAILED {
             return Err(MmapError::Initialize(std::io::Error::last_os_error()));
         }
 
-        Ok(Self {
+        let mmap = Self {
             address: NonNull::new(address).ok_or(MmapError::MmapIsNull)?,
             length,
-        })
+        };
+        if let Some(numa_node) = numa_node {
+            mmap.bind(numa_node)?;
+        }
+
+        Ok(mmap)
+    }
+
+    /// Binds the memory to `numa_node` with `mbind`. The mapping is fresh, so
+    /// no page has been allocated yet and none has to be moved.
+    fn bind(&self, numa_node: u32) -> Result<(), MmapError> {
+        unsafe { numa::bind_memory(self.address.as_ptr(), self.length, numa_node) }
+            .map_err(|error| MmapError::Bind(numa_node, error))
     }
 
     #[inline]
@@ -62,12 +158,102 @@ impl Mmap {
     }
 }
 
+/// Checks that `pool` has `required` free pages that are not reserved yet.
+fn check_hugepages(
+    page_size: PageSize,
+    required: u64,
+    pool: Option<HugepagePool>,
+) -> Result<(), MmapError> {
+    let Some(pool) = pool else {
+        return Err(MmapError::HugepagesUnsupported(page_size));
+    };
+    if pool.available() < required {
+        return Err(MmapError::InsufficientHugepages {
+            page_size,
+            required,
+            free: pool.available(),
+            total: pool.total,
+        });
+    }
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum MmapError {
     #[error("Failed to initialize Mmap: {0}")]
     Initialize(std::io::Error),
+    #[error("The kernel has no {0} hugepages. Enable them on the kernel command line")]
+    HugepagesUnsupported(PageSize),
+    #[error(
+        "{required} free {page_size} hugepages are needed but only {free} of {total} are free. \
+         Reserve more in /sys/kernel/mm/hugepages/hugepages-*/nr_hugepages"
+    )]
+    InsufficientHugepages {
+        page_size: PageSize,
+        required: u64,
+        free: u64,
+        total: u64,
+    },
+    #[error("Failed to read the hugepage pool: {0}")]
+    Hugepages(ProbeError),
+    #[error("Failed to bind Mmap to NUMA node {0}: {1}")]
+    Bind(u32, std::io::Error),
     #[error("Mmap returned Null. This is a bug.")]
     MmapIsNull,
     #[error("Failed to free Mmap: {0}")]
     Free(std::io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_to_node() {
+        // Node 0 exists on every system, but a node this large does not.
+        let mmap = Mmap::new(1 << 16, PageSize::Normal, Some(0));
+        if let Err(MmapError::Bind(_, error)) = &mmap {
+            // Kernels without NUMA support reject mbind.
+            assert_eq!(error.raw_os_error(), Some(libc::ENOSYS));
+            return;
+        }
+        let mmap = mmap.unwrap();
+        unsafe { mmap.as_ptr().cast::<u8>().write_bytes(0xff, mmap.length()) };
+
+        assert!(matches!(
+            Mmap::new(1 << 16, PageSize::Normal, Some(1023)),
+            Err(MmapError::Bind(1023, _))
+        ));
+    }
+
+    #[test]
+    fn round_up() {
+        assert_eq!(PageSize::Huge2MiB.round_up(1), 2 << 20);
+        assert_eq!(PageSize::Huge2MiB.round_up(4 << 20), 4 << 20);
+        assert_eq!(PageSize::Huge1GiB.pages((1 << 30) + 1), 2);
+        assert_eq!(PageSize::Normal.round_up(0), 0);
+    }
+
+    #[test]
+    fn hugepages() {
+        let pool = HugepagePool {
+            size: 2 << 20,
+            total: 64,
+            free: 6,
+            reserved: 2,
+        };
+        assert!(check_hugepages(PageSize::Huge2MiB, 4, Some(pool)).is_ok());
+        assert!(matches!(
+            check_hugepages(PageSize::Huge2MiB, 5, Some(pool)),
+            Err(MmapError::InsufficientHugepages {
+                required: 5,
+                free: 4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            check_hugepages(PageSize::Huge1GiB, 1, None),
+            Err(MmapError::HugepagesUnsupported(PageSize::Huge1GiB))
+        ));
+    }
+}
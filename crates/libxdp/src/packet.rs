@@ -0,0 +1,127 @@
+use crate::{descriptor::Descriptor, umem::Umem};
+
+/// A packet spread over one or more descriptors that are chained with
+/// [`Descriptor::continued`].
+#[derive(Clone, Copy, Debug)]
+pub struct Packet<'a> {
+    fragments: &'a [Descriptor],
+}
+
+impl<'a> Packet<'a> {
+    #[inline]
+    pub fn fragments(&self) -> &'a [Descriptor] {
+        self.fragments
+    }
+
+    /// Total length of the packet in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.fragments
+            .iter()
+            .map(|fragment| fragment.length as usize)
+            .sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the last fragment ends the packet. The rest of an incomplete
+    /// packet comes with the next read, which happens when the packet has
+    /// more fragments than the read could hold.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.fragments
+            .last()
+            .is_some_and(|fragment| !fragment.continued)
+    }
+
+    /// The bytes of each fragment in order.
+    #[inline]
+    pub fn slices<'u>(&self, umem: &'u Umem) -> impl Iterator<Item = &'u [u8]> {
+        self.fragments.iter().map(|fragment| {
+            let offset = umem.get_data(fragment.address) as *const u8;
+            unsafe { std::slice::from_raw_parts(offset, fragment.length as usize) }
+        })
+    }
+
+    /// Appends the joined bytes of all fragments to `buffer`.
+    pub fn copy_to(&self, umem: &Umem, buffer: &mut Vec<u8>) {
+        buffer.reserve(self.len());
+        for slice in self.slices(umem) {
+            buffer.extend_from_slice(slice);
+        }
+    }
+}
+
+/// Groups received descriptors into packets.
+///
+/// Without multi-buffer every descriptor is a packet of its own.
+#[derive(Clone, Debug)]
+pub struct Packets<'a> {
+    descriptors: &'a [Descriptor],
+}
+
+impl<'a> Packets<'a> {
+    #[inline]
+    pub fn new(descriptors: &'a [Descriptor]) -> Self {
+        Self { descriptors }
+    }
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = Packet<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.descriptors.is_empty() {
+            return None;
+        }
+
+        let length = self
+            .descriptors
+            .iter()
+            .position(|descriptor| !descriptor.continued)
+            .map_or(self.descriptors.len(), |index| index + 1);
+        let (fragments, rest) = self.descriptors.split_at(length);
+        self.descriptors = rest;
+        Some(Packet { fragments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(address: u64, continued: bool) -> Descriptor {
+        Descriptor {
+            address,
+            length: 100,
+            drop: false,
+            continued,
+        }
+    }
+
+    #[test]
+    fn packets() {
+        let descriptors = [
+            descriptor(0, false),
+            descriptor(2048, true),
+            descriptor(4096, true),
+            descriptor(6144, false),
+            descriptor(8192, true),
+        ];
+
+        let packets: Vec<_> = Packets::new(&descriptors).collect();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].fragments().len(), 1);
+        assert_eq!(packets[1].fragments().len(), 3);
+        assert_eq!(packets[1].len(), 300);
+        assert!(packets[1].is_complete());
+        assert_eq!(packets[2].fragments()[0].address, 8192);
+        assert!(!packets[2].is_complete());
+
+        assert_eq!(Packets::new(&[]).count(), 0);
+    }
+}
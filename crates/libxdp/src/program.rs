@@ -0,0 +1,293 @@
+use crate::socket::Socket;
+use mangonel_libxdp_sys::{
+    bpf_map__fd, bpf_map_delete_elem, bpf_map_update_elem, bpf_object__find_map_by_name,
+    libxdp_get_error, xdp_attach_mode, xdp_attach_mode_XDP_MODE_HW,
+    xdp_attach_mode_XDP_MODE_NATIVE, xdp_attach_mode_XDP_MODE_SKB, xdp_attach_mode_XDP_MODE_UNSPEC,
+    xdp_program, xdp_program__attach, xdp_program__bpf_obj, xdp_program__close,
+    xdp_program__detach, xdp_program__fd, xdp_program__is_attached, xdp_program__open_file,
+};
+use std::{
+    ffi::{CString, NulError},
+    marker::PhantomData,
+    path::Path,
+    ptr::{NonNull, null_mut},
+};
+
+/// Where the kernel runs an attached XDP program.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttachMode {
+    /// Let libxdp pick native mode and fall back to SKB mode.
+    #[default]
+    Auto,
+    /// Generic XDP in the network stack (`XDP_FLAGS_SKB_MODE`).
+    Skb,
+    /// XDP in the driver (`XDP_FLAGS_DRV_MODE`).
+    Native,
+    /// XDP offloaded to the NIC (`XDP_FLAGS_HW_MODE`).
+    Offload,
+}
+
+impl AttachMode {
+    fn as_raw(self) -> xdp_attach_mode {
+        match self {
+            Self::Auto => xdp_attach_mode_XDP_MODE_UNSPEC,
+            Self::Skb => xdp_attach_mode_XDP_MODE_SKB,
+            Self::Native => xdp_attach_mode_XDP_MODE_NATIVE,
+            Self::Offload => xdp_attach_mode_XDP_MODE_HW,
+        }
+    }
+
+    fn from_raw(mode: xdp_attach_mode) -> Option<Self> {
+        [Self::Skb, Self::Native, Self::Offload]
+            .into_iter()
+            .find(|attach_mode| attach_mode.as_raw() == mode)
+    }
+}
+
+/// An XDP program loaded from a BPF object file.
+///
+/// Use it together with [`SocketBuilder::inhibit_program_load`] so that
+/// libxdp does not attach its default redirect program in its place. The
+/// program is detached when dropped, and a failure to do so is logged.
+///
+/// [`SocketBuilder::inhibit_program_load`]: crate::SocketBuilder::inhibit_program_load
+#[derive(Debug)]
+pub struct XdpProgram {
+    program: NonNull<xdp_program>,
+    attachment: Option<(u32, AttachMode)>,
+}
+
+// SAFETY: The program handle is owned exclusively by XdpProgram and libxdp
+// keeps no thread-local state for it.
+unsafe impl Send for XdpProgram {}
+
+impl Drop for XdpProgram {
+    fn drop(&mut self) {
+        if let Err(error) = self.detach() {
+            tracing::error!("{error}. The program may still be attached");
+        }
+        unsafe { xdp_program__close(self.program.as_ptr()) };
+    }
+}
+
+impl XdpProgram {
+    /// Opens a BPF object file. `section_name` selects the program when the
+    /// object contains more than one; `None` takes the first one.
+    pub fn open_file(
+        path: impl AsRef<Path>,
+        section_name: Option<&str>,
+    ) -> Result<Self, XdpProgramError> {
+        let path = path.as_ref();
+        let path = CString::new(path.as_os_str().as_encoded_bytes())
+            .map_err(XdpProgramError::InvalidName)?;
+        let section_name = section_name
+            .map(CString::new)
+            .transpose()
+            .map_err(XdpProgramError::InvalidName)?;
+
+        let program = unsafe {
+            xdp_program__open_file(
+                path.as_ptr(),
+                section_name
+                    .as_ref()
+                    .map_or(std::ptr::null(), |name| name.as_ptr()),
+                null_mut(),
+            )
+        };
+        let value = unsafe { libxdp_get_error(program as *const _) };
+        if value != 0 {
+            return Err(XdpProgramError::Open(std::io::Error::from_raw_os_error(
+                -value as i32,
+            )));
+        }
+
+        Ok(Self {
+            program: NonNull::new(program).ok_or(XdpProgramError::ProgramIsNull)?,
+            attachment: None,
+        })
+    }
+
+    /// Attaches the program to an interface. A program can be attached to
+    /// one interface at a time. With [`AttachMode::Auto`], the mode that
+    /// libxdp picked is read back with [`XdpProgram::attach_mode`].
+    pub fn attach(
+        &mut self,
+        interface_name: impl AsRef<str>,
+        mode: AttachMode,
+    ) -> Result<(), XdpProgramError> {
+        if self.attachment.is_some() {
+            return Err(XdpProgramError::AlreadyAttached);
+        }
+
+        let interface_index = interface_index(interface_name.as_ref())?;
+        let value = unsafe {
+            xdp_program__attach(
+                self.program.as_ptr(),
+                interface_index as i32,
+                mode.as_raw(),
+                0,
+            )
+        };
+        if value.is_negative() {
+            return Err(XdpProgramError::Attach(std::io::Error::from_raw_os_error(
+                -value,
+            )));
+        }
+
+        // Detaching needs the mode that the program runs in, not `Auto`.
+        let mode = match mode {
+            AttachMode::Auto => AttachMode::from_raw(unsafe {
+                xdp_program__is_attached(self.program.as_ptr(), interface_index as i32)
+            })
+            .unwrap_or(mode),
+            mode => mode,
+        };
+        self.attachment = Some((interface_index, mode));
+        Ok(())
+    }
+
+    /// The mode the program was attached in, or `None` when it is not
+    /// attached.
+    #[inline]
+    pub fn attach_mode(&self) -> Option<AttachMode> {
+        self.attachment.map(|(_, mode)| mode)
+    }
+
+    /// Detaches the program from its interface, if it is attached. The
+    /// program stays attached on error.
+    pub fn detach(&mut self) -> Result<(), XdpProgramError> {
+        let Some((interface_index, mode)) = self.attachment else {
+            return Ok(());
+        };
+
+        let value = unsafe {
+            xdp_program__detach(
+                self.program.as_ptr(),
+                interface_index as i32,
+                mode.as_raw(),
+                0,
+            )
+        };
+        if value.is_negative() {
+            return Err(XdpProgramError::Detach(std::io::Error::from_raw_os_error(
+                -value,
+            )));
+        }
+
+        self.attachment = None;
+        Ok(())
+    }
+
+    /// Looks up an `XSKMAP` defined by the program.
+    pub fn xsk_map(&self, name: &str) -> Result<XskMap<'_>, XdpProgramError> {
+        let c_name = CString::new(name).map_err(XdpProgramError::InvalidName)?;
+        let map = unsafe {
+            let object = xdp_program__bpf_obj(self.program.as_ptr());
+            if object.is_null() {
+                return Err(XdpProgramError::MapNotFound(name.to_owned()));
+            }
+            bpf_object__find_map_by_name(object, c_name.as_ptr())
+        };
+        if map.is_null() {
+            return Err(XdpProgramError::MapNotFound(name.to_owned()));
+        }
+
+        let fd = unsafe { bpf_map__fd(map) };
+        if fd.is_negative() {
+            return Err(XdpProgramError::MapNotLoaded(name.to_owned()));
+        }
+
+        Ok(XskMap {
+            fd,
+            _program: PhantomData,
+        })
+    }
+
+    #[inline]
+    pub fn fd(&self) -> i32 {
+        unsafe { xdp_program__fd(self.program.as_ptr()) }
+    }
+}
+
+/// An `XSKMAP` that redirects packets from the XDP program to AF_XDP
+/// sockets, keyed by queue ID.
+#[derive(Debug)]
+pub struct XskMap<'a> {
+    fd: i32,
+    _program: PhantomData<&'a XdpProgram>,
+}
+
+impl XskMap<'_> {
+    /// Registers `socket` under `queue_id`.
+    pub fn insert(&self, queue_id: u32, socket: &Socket) -> Result<(), XdpProgramError> {
+        let socket_fd = socket.socket_fd();
+        let value = unsafe {
+            bpf_map_update_elem(
+                self.fd,
+                &queue_id as *const u32 as *const _,
+                &socket_fd as *const i32 as *const _,
+                0,
+            )
+        };
+        if value.is_negative() {
+            return Err(XdpProgramError::UpdateMap(
+                std::io::Error::from_raw_os_error(-value),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the socket registered under `queue_id`.
+    pub fn remove(&self, queue_id: u32) -> Result<(), XdpProgramError> {
+        let value = unsafe { bpf_map_delete_elem(self.fd, &queue_id as *const u32 as *const _) };
+        if value.is_negative() {
+            return Err(XdpProgramError::UpdateMap(
+                std::io::Error::from_raw_os_error(-value),
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+}
+
+fn interface_index(interface_name: &str) -> Result<u32, XdpProgramError> {
+    let c_name = CString::new(interface_name).map_err(XdpProgramError::InvalidName)?;
+    let interface_index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if interface_index == 0 {
+        return Err(XdpProgramError::InterfaceNotFound(
+            interface_name.to_owned(),
+        ));
+    }
+
+    Ok(interface_index)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum XdpProgramError {
+    #[error("Name contains null character(s): {0}")]
+    InvalidName(NulError),
+    #[error("Failed to open XDP program: {0}")]
+    Open(std::io::Error),
+    #[error("XDP program returned Null. This is a bug.")]
+    ProgramIsNull,
+    #[error("Interface not found: {0}")]
+    InterfaceNotFound(String),
+    #[error("XDP program is already attached")]
+    AlreadyAttached,
+    #[error("Failed to attach XDP program: {0}")]
+    Attach(std::io::Error),
+    #[error("Failed to detach XDP program: {0}")]
+    Detach(std::io::Error),
+    #[error("Map not found: {0}")]
+    MapNotFound(String),
+    #[error("Map is not loaded into the kernel: {0}")]
+    MapNotLoaded(String),
+    #[error("Failed to update XSKMAP: {0}")]
+    UpdateMap(std::io::Error),
+}
@@ -1,7 +1,8 @@
 use mangonel_libxdp_sys::{
-    xdp_desc, xsk_ring_cons, xsk_ring_cons__comp_addr, xsk_ring_cons__peek, xsk_ring_cons__release,
-    xsk_ring_cons__rx_desc, xsk_ring_prod, xsk_ring_prod__fill_addr, xsk_ring_prod__reserve,
-    xsk_ring_prod__submit, xsk_ring_prod__tx_desc,
+    xdp_desc, xsk_ring_cons, xsk_ring_cons__cancel, xsk_ring_cons__comp_addr, xsk_ring_cons__peek,
+    xsk_ring_cons__release, xsk_ring_cons__rx_desc, xsk_ring_prod, xsk_ring_prod__fill_addr,
+    xsk_ring_prod__needs_wakeup, xsk_ring_prod__reserve, xsk_ring_prod__submit,
+    xsk_ring_prod__tx_desc,
 };
 use std::mem::MaybeUninit;
 
@@ -70,6 +71,13 @@ impl Producer {
     pub fn submit(&self, offset: u32) {
         unsafe { xsk_ring_prod__submit(self.as_ptr(), offset) };
     }
+
+    /// Returns `true` when the kernel has set `XDP_RING_NEED_WAKEUP` on this
+    /// ring. Only meaningful for sockets bound with `XDP_USE_NEED_WAKEUP`.
+    #[inline]
+    pub fn needs_wakeup(&self) -> bool {
+        unsafe { xsk_ring_prod__needs_wakeup(self.as_ptr()) != 0 }
+    }
 }
 
 /// Ring consumer handle.
@@ -114,6 +122,13 @@ impl Consumer {
         }
     }
 
+    /// Hands back the last `offset` peeked entries, so that the next peek
+    /// returns them again.
+    #[inline]
+    pub fn cancel(&self, offset: u32) {
+        unsafe { xsk_ring_cons__cancel(self.as_ptr(), offset) };
+    }
+
     #[inline]
     pub fn release(&self, offset: u32) {
         unsafe { xsk_ring_cons__release(self.as_ptr(), offset) };
@@ -1,32 +1,93 @@
 use crate::{
+    allocator::{FrameAllocation, FrameAllocator},
     descriptor::Descriptor,
-    mmap::{Mmap, MmapError},
+    frame::Frame,
+    mmap::{Mmap, MmapError, PageSize},
     ring::{Consumer, Producer, RingError, ring_buffer},
+    stats::{SocketCounters, StatsError, XdpStatistics},
     umem::{Umem, UmemError},
     util,
 };
-use libc::{MSG_DONTWAIT, POLLIN, poll, pollfd, sendto};
+use libc::{
+    MSG_DONTWAIT, POLLERR, POLLHUP, POLLIN, SOL_XDP, XDP_OPTIONS, XDP_OPTIONS_ZEROCOPY, getsockopt,
+    poll, pollfd, sendto, socklen_t, xdp_options,
+};
 use mangonel_libxdp_sys::{
-    XDP_COPY, XDP_ZEROCOPY, XSK_RING_PROD__DEFAULT_NUM_DESCS, XSK_UMEM__DEFAULT_FRAME_HEADROOM,
-    XSK_UMEM__DEFAULT_FRAME_SIZE, xsk_socket, xsk_socket__create, xsk_socket__delete,
-    xsk_socket__fd, xsk_socket_config, xsk_socket_config__bindgen_ty_1,
+    XDP_COPY, XDP_PKT_CONTD, XDP_USE_NEED_WAKEUP, XDP_USE_SG, XDP_ZEROCOPY,
+    XSK_LIBBPF_FLAGS__INHIBIT_PROG_LOAD, XSK_RING_PROD__DEFAULT_NUM_DESCS,
+    XSK_UMEM__DEFAULT_FRAME_HEADROOM, XSK_UMEM__DEFAULT_FRAME_SIZE, xsk_socket, xsk_socket__create,
+    xsk_socket__create_shared, xsk_socket__delete, xsk_socket__fd, xsk_socket_config,
+    xsk_socket_config__bindgen_ty_1,
 };
+use mangonel_util::capability::{Capabilities, HugepagePool};
 use std::{
     ffi::{CString, NulError},
+    mem::MaybeUninit,
     ptr::{NonNull, null_mut},
-    sync::{
-        Arc,
-        mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
-    },
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-#[derive(Debug)]
+/// How a socket is bound to its queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BindMode {
+    /// Zero-copy when the driver supports it, and copy mode otherwise. The
+    /// kernel tries zero-copy first and falls back on its own.
+    #[default]
+    Auto,
+    /// The driver reads and writes the UMEM directly (`XDP_ZEROCOPY`).
+    /// Building the socket fails when the driver does not support it.
+    ZeroCopy,
+    /// The kernel copies frames between the UMEM and the driver buffers
+    /// (`XDP_COPY`). This works with every driver.
+    Copy,
+}
+
+impl BindMode {
+    fn bind_flags(self) -> u16 {
+        match self {
+            Self::Auto => 0,
+            Self::ZeroCopy => XDP_ZEROCOPY as u16,
+            Self::Copy => XDP_COPY as u16,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct SocketBuilder {
     pub frame_size: u32,
     pub frame_headroom_size: u32,
+    /// Number of frames in the UMEM. Defaults to `ring_size` when `None`.
+    /// Set this to a multiple of `ring_size` to share the UMEM with other
+    /// sockets through [`SocketBuilder::build_shared`].
+    pub frame_count: Option<u32>,
     pub ring_size: u32,
-    pub use_hugetlb: bool,
-    pub force_zero_copy: bool,
+    /// Backs the UMEM with hugepages when not [`PageSize::Normal`]. The
+    /// UMEM is rounded up to whole pages.
+    pub page_size: PageSize,
+    /// Binds the UMEM memory to this NUMA node. Use the node of the
+    /// interface so that the NIC does not DMA across nodes. The kernel
+    /// places the memory when `None`.
+    pub numa_node: Option<u32>,
+    /// Check the mode that was negotiated with [`Socket::bind_mode`].
+    pub bind_mode: BindMode,
+    /// Binds with `XDP_USE_NEED_WAKEUP` so that [`TxSocket`] and [`RxSocket`]
+    /// only issue syscalls when the kernel asks for a wakeup.
+    pub use_need_wakeup: bool,
+    /// Binds with `XDP_USE_SG` so that packets larger than a frame, such as
+    /// jumbo frames, are chained over several descriptors. Group them with
+    /// [`Packets`]. An [`XdpProgram`] has to be built for `xdp.frags`.
+    ///
+    /// [`Packets`]: crate::Packets
+    /// [`XdpProgram`]: crate::XdpProgram
+    pub use_multi_buffer: bool,
+    pub frame_allocation: FrameAllocation,
+    /// Sets `XSK_LIBBPF_FLAGS__INHIBIT_PROG_LOAD` so that libxdp does not
+    /// load its default redirect program. Register the sockets into the
+    /// `XSKMAP` of an [`XdpProgram`] instead.
+    ///
+    /// [`XdpProgram`]: crate::XdpProgram
+    pub inhibit_program_load: bool,
 }
 
 impl Default for SocketBuilder {
@@ -34,14 +95,70 @@ impl Default for SocketBuilder {
         Self {
             frame_size: XSK_UMEM__DEFAULT_FRAME_SIZE,
             frame_headroom_size: XSK_UMEM__DEFAULT_FRAME_HEADROOM,
+            frame_count: None,
             ring_size: XSK_RING_PROD__DEFAULT_NUM_DESCS,
-            use_hugetlb: false,
-            force_zero_copy: false,
+            page_size: PageSize::Normal,
+            numa_node: None,
+            bind_mode: BindMode::Auto,
+            use_need_wakeup: false,
+            use_multi_buffer: false,
+            frame_allocation: FrameAllocation::Shared,
+            inhibit_program_load: false,
         }
     }
 }
 
 impl SocketBuilder {
+    /// Turns off the options that `capabilities` reports as unsupported, so
+    /// that [`SocketBuilder::build`] does not fail on them. Options that
+    /// could not be checked stay as they are.
+    ///
+    /// Probe the interface with [`Capabilities::probe_interface`] so that
+    /// zero-copy is checked as well.
+    pub fn with_capabilities(mut self, capabilities: &Capabilities) -> Self {
+        if capabilities.need_wakeup.is_unsupported() {
+            self.use_need_wakeup = false;
+        }
+        if capabilities
+            .interface
+            .is_some_and(|interface| interface.zero_copy.is_unsupported())
+        {
+            self.bind_mode = BindMode::Copy;
+        }
+        if capabilities.multi_buffer.is_unsupported()
+            || capabilities
+                .interface
+                .is_some_and(|interface| interface.multi_buffer.is_unsupported())
+        {
+            self.use_multi_buffer = false;
+        }
+        if self.page_size.is_huge() {
+            let required = self.page_size.pages(self.umem_length()) as u64;
+            let pool = match self.numa_node {
+                // The pools in `capabilities` count the pages of every node.
+                Some(numa_node) => HugepagePool::read(self.page_size.bytes(), Some(numa_node)),
+                None => Ok(capabilities
+                    .hugepages
+                    .iter()
+                    .find(|pool| pool.size == self.page_size.bytes())
+                    .copied()),
+            };
+            match pool {
+                Ok(Some(pool)) if pool.available() >= required => {}
+                Ok(_) => self.page_size = PageSize::Normal,
+                // A pool that could not be read was not checked.
+                Err(_) => {}
+            }
+        }
+        self
+    }
+
+    /// Size of the UMEM in bytes before rounding to the page size.
+    fn umem_length(&self) -> usize {
+        let frame_count = self.frame_count.unwrap_or(self.ring_size);
+        (self.frame_size + self.frame_headroom_size) as usize * frame_count as usize
+    }
+
     /// # Panics
     ///
     /// The function panics when [`setrlimit()`] panic conditions are met.
@@ -50,15 +167,23 @@ impl SocketBuilder {
         interface_name: impl AsRef<str>,
         queue_id: u32,
     ) -> Result<(TxSocket, RxSocket, Umem), SocketError> {
-        Socket::init(
-            self.frame_size,
-            self.frame_headroom_size,
-            self.ring_size,
-            self.use_hugetlb,
-            self.force_zero_copy,
-            interface_name,
-            queue_id,
-        )
+        Socket::init(self, interface_name, queue_id)
+    }
+
+    /// Builds a socket pair on top of an existing [`Umem`].
+    ///
+    /// The new socket gets its own fill and completion rings and claims
+    /// `ring_size` frames from `umem`, so it can be bound to another queue or
+    /// interface. `frame_size`, `frame_headroom_size`, `frame_count`,
+    /// `page_size` and `numa_node` are ignored because the UMEM already
+    /// exists.
+    pub fn build_shared(
+        self,
+        umem: &Umem,
+        interface_name: impl AsRef<str>,
+        queue_id: u32,
+    ) -> Result<(TxSocket, RxSocket), SocketError> {
+        Socket::init_shared(self, umem, interface_name, queue_id)
     }
 }
 
@@ -66,7 +191,11 @@ pub struct Socket {
     inner: Arc<SocketInner>,
 }
 
-struct SocketInner(NonNull<xsk_socket>);
+struct SocketInner {
+    socket: NonNull<xsk_socket>,
+    umem: Umem,
+    bind_mode: BindMode,
+}
 
 // SAFETY: SocketInner is only accessed via xsk_socket__fd (read-only) and
 // xsk_socket__delete (in Drop, which runs only after all Arc refs are gone).
@@ -75,8 +204,10 @@ unsafe impl Send for SocketInner {}
 unsafe impl Sync for SocketInner {}
 
 impl Drop for SocketInner {
+    /// Deletes the socket. Its frames go back to the [`Umem`] once the
+    /// [`FrameAllocator`]s and the [`Frame`]s using them are dropped as well.
     fn drop(&mut self) {
-        unsafe { xsk_socket__delete(self.0.as_ptr()) }
+        unsafe { xsk_socket__delete(self.socket.as_ptr()) }
     }
 }
 
@@ -91,11 +222,7 @@ impl Clone for Socket {
 
 impl Socket {
     pub fn init(
-        frame_size: u32,
-        frame_headroom_size: u32,
-        ring_size: u32,
-        use_hugetlb: bool,
-        force_zero_copy: bool,
+        builder: SocketBuilder,
         interface_name: impl AsRef<str>,
         queue_id: u32,
     ) -> Result<(TxSocket, RxSocket, Umem), SocketError> {
@@ -103,114 +230,377 @@ impl Socket {
         util::setrlimit().map_err(SocketError::Setrlimit)?;
 
         // Initialize the memory map.
-        let length = (frame_size + frame_headroom_size) * ring_size;
-        let mmap = Mmap::new(length as usize, use_hugetlb)?;
+        let frame_count = builder.frame_count.unwrap_or(builder.ring_size);
+        let mmap = Mmap::new(builder.umem_length(), builder.page_size, builder.numa_node)?;
 
         // Initialize XDP UMEM.
-        let (umem, fill_ring, completion_ring) =
-            Umem::new(mmap, frame_size, frame_headroom_size, ring_size)?;
+        let (umem, fill_ring, completion_ring) = Umem::new(
+            mmap,
+            builder.frame_size,
+            builder.frame_headroom_size,
+            frame_count,
+            builder.ring_size,
+        )?;
+
+        // The first socket uses the fill and completion rings that were
+        // registered together with the UMEM.
+        let (tx_socket, rx_socket) = Self::create(
+            &builder,
+            &umem,
+            fill_ring,
+            completion_ring,
+            false,
+            interface_name,
+            queue_id,
+        )?;
+
+        Ok((tx_socket, rx_socket, umem))
+    }
+
+    pub fn init_shared(
+        builder: SocketBuilder,
+        umem: &Umem,
+        interface_name: impl AsRef<str>,
+        queue_id: u32,
+    ) -> Result<(TxSocket, RxSocket), SocketError> {
+        // Every socket sharing the UMEM needs its own fill and completion
+        // rings, sized like the ones created with the UMEM.
+        let (fill_ring, completion_ring) = ring_buffer(umem.config().fill_size)?;
+
+        Self::create(
+            &builder,
+            umem,
+            fill_ring,
+            completion_ring,
+            true,
+            interface_name,
+            queue_id,
+        )
+    }
+
+    fn create(
+        builder: &SocketBuilder,
+        umem: &Umem,
+        fill_ring: Producer,
+        completion_ring: Consumer,
+        shared: bool,
+        interface_name: impl AsRef<str>,
+        queue_id: u32,
+    ) -> Result<(TxSocket, RxSocket), SocketError> {
+        let ring_size = builder.ring_size;
 
         // Initialize XDP socket.
         let mut socket = null_mut();
 
         let interface_name =
             CString::new(interface_name.as_ref()).map_err(SocketError::InvalidInterfaceName)?;
+        if unsafe { libc::if_nametoindex(interface_name.as_ptr()) } == 0 {
+            return Err(SocketError::InterfaceNotFound(
+                interface_name.to_string_lossy().into_owned(),
+            ));
+        }
 
-        let mut xdp_flags = 0;
-        match force_zero_copy {
-            true => xdp_flags |= XDP_ZEROCOPY,
-            false => xdp_flags |= XDP_COPY,
+        let mut bind_flags = builder.bind_mode.bind_flags();
+        if builder.use_need_wakeup {
+            bind_flags |= XDP_USE_NEED_WAKEUP as u16;
+        }
+        if builder.use_multi_buffer {
+            bind_flags |= XDP_USE_SG as u16;
+        }
+
+        let mut libbpf_flags = 0;
+        if builder.inhibit_program_load {
+            libbpf_flags |= XSK_LIBBPF_FLAGS__INHIBIT_PROG_LOAD;
         }
 
         let socket_config = xsk_socket_config {
             rx_size: ring_size,
             tx_size: ring_size,
-            __bindgen_anon_1: xsk_socket_config__bindgen_ty_1 { libbpf_flags: 0 },
-            xdp_flags,
-            bind_flags: 0,
+            __bindgen_anon_1: xsk_socket_config__bindgen_ty_1 { libbpf_flags },
+            // libxdp attaches its program in native mode and falls back to SKB
+            // mode.
+            xdp_flags: 0,
+            bind_flags,
         };
 
         let (tx_ring, rx_ring) = ring_buffer(ring_size)?;
 
+        // Claim the frames before creating the socket so that a UMEM without
+        // enough free frames fails early.
+        let claim = Arc::new(umem.claim_frames(ring_size)?);
+
         let value = unsafe {
-            xsk_socket__create(
-                &mut socket,
-                interface_name.as_ptr(),
-                queue_id,
-                umem.as_ptr(),
-                rx_ring.as_ptr(),
-                tx_ring.as_ptr(),
-                &socket_config,
-            )
+            match shared {
+                true => xsk_socket__create_shared(
+                    &mut socket,
+                    interface_name.as_ptr(),
+                    queue_id,
+                    umem.as_ptr(),
+                    rx_ring.as_ptr(),
+                    tx_ring.as_ptr(),
+                    fill_ring.as_ptr(),
+                    completion_ring.as_ptr(),
+                    &socket_config,
+                ),
+                false => xsk_socket__create(
+                    &mut socket,
+                    interface_name.as_ptr(),
+                    queue_id,
+                    umem.as_ptr(),
+                    rx_ring.as_ptr(),
+                    tx_ring.as_ptr(),
+                    &socket_config,
+                ),
+            }
         };
         if value.is_negative() {
-            return Err(SocketError::Initialize(std::io::Error::from_raw_os_error(
-                -value,
-            )));
+            let error = std::io::Error::from_raw_os_error(-value);
+            return Err(match builder.bind_mode {
+                BindMode::ZeroCopy if -value == libc::EOPNOTSUPP => {
+                    SocketError::ZeroCopyUnsupported(error)
+                }
+                _ => SocketError::Initialize(error),
+            });
         }
 
-        let socket = Self {
-            inner: SocketInner(NonNull::new(socket).ok_or(SocketError::SocketIsNull)?).into(),
+        let Some(socket) = NonNull::new(socket) else {
+            return Err(SocketError::SocketIsNull);
         };
 
-        // Prefill the descriptor buffer.
-        let (descriptor_writer, descriptor_reader) = mpsc::sync_channel(ring_size as usize);
-        (0..ring_size).for_each(|descriptor_index| {
-            let address = descriptor_index * (frame_size + frame_headroom_size);
-            descriptor_writer.try_send(address as u64).unwrap();
-        });
+        let bind_mode = match read_bind_mode(unsafe { xsk_socket__fd(socket.as_ptr()) }) {
+            Ok(bind_mode) => bind_mode,
+            Err(error) => {
+                unsafe { xsk_socket__delete(socket.as_ptr()) };
+                return Err(SocketError::BindMode(error));
+            }
+        };
+
+        // Hand the claimed frames over to the allocators.
+        let frame_stride = umem.frame_stride();
+        let frames = claim.frames().to_vec();
+        let (tx_allocator, rx_allocator) = match builder.frame_allocation {
+            FrameAllocation::Shared => {
+                let rx_allocator =
+                    FrameAllocator::new_shared(frames, frame_stride).with_claim(claim);
+                let tx_allocator = rx_allocator.share().unwrap();
+                (tx_allocator, rx_allocator)
+            }
+            FrameAllocation::Split { tx_frame_count } => {
+                let mut rx_frames = frames;
+                let split_index = rx_frames.len() - (tx_frame_count as usize).min(rx_frames.len());
+                let tx_frames = rx_frames.split_off(split_index);
+                (
+                    FrameAllocator::new(tx_frames, frame_stride).with_claim(claim.clone()),
+                    FrameAllocator::new(rx_frames, frame_stride).with_claim(claim),
+                )
+            }
+        };
+
+        let socket = Self {
+            inner: SocketInner {
+                socket,
+                umem: umem.clone(),
+                bind_mode,
+            }
+            .into(),
+        };
 
         let tx_socket = TxSocket {
             socket: socket.clone(),
             ring_size,
+            use_need_wakeup: builder.use_need_wakeup,
             completion_ring,
             tx_ring,
-            descriptor_writer,
+            frame_allocator: tx_allocator,
+            counters: SocketCounters::default(),
+            outstanding: 0,
         };
         let rx_socket = RxSocket {
             socket,
             ring_size,
+            use_need_wakeup: builder.use_need_wakeup,
             fill_ring,
             rx_ring,
-            descriptor_reader,
+            frame_allocator: rx_allocator,
+            fill_buffer: Vec::with_capacity(ring_size as usize),
+            counters: SocketCounters::default(),
         };
 
-        Ok((tx_socket, rx_socket, umem))
+        Ok((tx_socket, rx_socket))
     }
 
     #[inline]
     pub fn socket_fd(&self) -> i32 {
-        unsafe { xsk_socket__fd(self.inner.0.as_ptr()) }
+        unsafe { xsk_socket__fd(self.inner.socket.as_ptr()) }
+    }
+
+    /// The mode that the kernel bound the socket in, either
+    /// [`BindMode::ZeroCopy`] or [`BindMode::Copy`].
+    #[inline]
+    pub fn bind_mode(&self) -> BindMode {
+        self.inner.bind_mode
+    }
+
+    #[inline]
+    pub fn is_zero_copy(&self) -> bool {
+        self.inner.bind_mode == BindMode::ZeroCopy
     }
 }
+
+/// Reads the negotiated mode of a bound socket with `XDP_OPTIONS`.
+fn read_bind_mode(socket_fd: i32) -> Result<BindMode, std::io::Error> {
+    let mut options = MaybeUninit::<xdp_options>::zeroed();
+    let mut length = size_of::<xdp_options>() as socklen_t;
+    let value = unsafe {
+        getsockopt(
+            socket_fd,
+            SOL_XDP,
+            XDP_OPTIONS,
+            options.as_mut_ptr() as *mut _,
+            &mut length,
+        )
+    };
+    if value.is_negative() {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let options = unsafe { options.assume_init() };
+    Ok(match options.flags & XDP_OPTIONS_ZEROCOPY {
+        0 => BindMode::Copy,
+        _ => BindMode::ZeroCopy,
+    })
+}
+
 pub struct TxSocket {
+    // Dropped before `frame_allocator`, so that the kernel is done with the
+    // frames before they can go back to the UMEM.
     socket: Socket,
     ring_size: u32,
+    use_need_wakeup: bool,
     completion_ring: Consumer,
     tx_ring: Producer,
-    descriptor_writer: SyncSender<u64>,
+    frame_allocator: FrameAllocator,
+    counters: SocketCounters,
+    outstanding: u32,
+}
+
+/// What a call to [`TxSocket::write`] or [`TxSocket::reclaim`] did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TxOutcome {
+    /// Descriptors taken from the front of the batch and handed to the
+    /// kernel. The rest of the batch was not submitted and is still owned by
+    /// the caller.
+    pub accepted: u32,
+    /// Frames the kernel finished sending during this call. They are back in
+    /// the pool.
+    pub completed: u32,
+    /// Frames submitted and not completed yet, i.e. frames the kernel owns.
+    pub outstanding: u32,
 }
 
 impl TxSocket {
+    /// Submits descriptors from the front of `buffer`.
+    ///
+    /// At most `ring_size` descriptors are submitted, and fewer when the TX
+    /// ring is full. Only `buffer[..accepted]` belongs to the kernel
+    /// afterwards; retry the rest later. All pending completions are drained
+    /// before returning.
+    ///
+    /// `accepted` can end in the middle of a chained packet. The kernel sends
+    /// the packet once the rest of it is submitted, so retry the rest before
+    /// any other packet.
     #[inline]
-    pub fn write(&mut self, buffer: &[Descriptor]) -> u32 {
+    pub fn write(&mut self, buffer: &[Descriptor]) -> TxOutcome {
         let size = self.ring_size.min(buffer.len() as u32);
+        self.transmit(size, |offset| &buffer[offset as usize])
+    }
+
+    /// Sends frames from the front of `frames` and removes the ones the
+    /// kernel accepted. Frames left in `frames` were not sent and can be
+    /// retried.
+    #[inline]
+    pub fn write_frames(&mut self, frames: &mut Vec<Frame<'_>>) -> TxOutcome {
+        let size = self.ring_size.min(frames.len() as u32);
+        let outcome = self.transmit(size, |offset| frames[offset as usize].descriptor());
+        frames.drain(..outcome.accepted as usize).for_each(|frame| {
+            frame.into_descriptor();
+        });
+        outcome
+    }
+
+    /// Drains the completion ring without submitting anything, e.g. while
+    /// waiting for outstanding frames before shutting down or retrying.
+    #[inline]
+    pub fn reclaim(&mut self) -> TxOutcome {
+        if self.outstanding > 0 {
+            self.send();
+        }
+        let completed = self.complete(|_| ());
+        TxOutcome {
+            accepted: 0,
+            completed,
+            outstanding: self.outstanding,
+        }
+    }
+
+    /// Like [`TxSocket::reclaim`], but also appends the address of every
+    /// completed frame to `addresses`.
+    pub fn reclaim_addresses(&mut self, addresses: &mut Vec<u64>) -> TxOutcome {
+        if self.outstanding > 0 {
+            self.send();
+        }
+        let completed = self.complete(|address| addresses.push(address));
+        TxOutcome {
+            accepted: 0,
+            completed,
+            outstanding: self.outstanding,
+        }
+    }
+
+    /// Number of frames submitted and not completed yet.
+    #[inline]
+    pub fn outstanding(&self) -> u32 {
+        self.outstanding
+    }
+
+    #[inline]
+    fn transmit<'a>(&mut self, size: u32, descriptor: impl Fn(u32) -> &'a Descriptor) -> TxOutcome {
         let (tx_available, tx_index) = self.tx_ring.reserve(size);
         let mut offset: u32 = 0;
+        let mut bytes: u64 = 0;
         while offset < tx_available {
             let descriptor_mut = self.tx_ring.descriptor(tx_index + offset);
-            descriptor_mut.addr = buffer[offset as usize].address;
-            descriptor_mut.len = buffer[offset as usize].length;
+            let descriptor = descriptor(offset);
+            descriptor_mut.addr = descriptor.address;
+            descriptor_mut.len = descriptor.length;
+            descriptor_mut.options = match descriptor.continued {
+                true => XDP_PKT_CONTD,
+                false => 0,
+            };
+            bytes += descriptor.length as u64;
             offset += 1;
         }
         self.tx_ring.submit(offset);
+        self.outstanding += offset;
+        self.counters.record_batch(offset, bytes);
         self.send();
-        self.complete(size);
-        offset
+        let completed = self.complete(|_| ());
+        TxOutcome {
+            accepted: offset,
+            completed,
+            outstanding: self.outstanding,
+        }
     }
 
+    /// Kicks the kernel to process the TX ring. With need_wakeup enabled, the
+    /// syscall is skipped unless the kernel has flagged the TX ring.
     #[inline]
     fn send(&mut self) {
+        if self.use_need_wakeup && !self.tx_ring.needs_wakeup() {
+            return;
+        }
+
+        self.counters.record_wakeup();
         unsafe {
             sendto(
                 self.socket.socket_fd(),
@@ -223,30 +613,147 @@ impl TxSocket {
         };
     }
 
+    /// Returns every completed frame to the pool and reports its address to
+    /// `f`. Frames of other pools, e.g. the RX pool with
+    /// [`FrameAllocation::Split`], are skipped and counted.
     #[inline]
-    fn complete(&mut self, size: u32) {
-        let (filled, index) = self.completion_ring.peek(size);
+    fn complete(&mut self, mut f: impl FnMut(u64)) -> u32 {
+        let (filled, index) = self.completion_ring.peek(u32::MAX);
         let mut offset: u32 = 0;
         while offset < filled {
-            let address = self.completion_ring.completion_address(index + offset);
-            match self.descriptor_writer.try_send(*address) {
-                Err(TrySendError::Full(_)) => break,
-                Err(TrySendError::Disconnected(_)) => {
-                    panic!("Descriptor sender disconnected. This is a bug.");
+            let address = *self.completion_ring.completion_address(index + offset);
+            match self.frame_allocator.try_free(address) {
+                Ok(()) => f(address),
+                Err(error) => {
+                    self.counters.record_foreign_completion();
+                    tracing::warn!("Skipped a completion of the TX ring: {error}");
                 }
-                Ok(_) => offset += 1,
             }
+            offset += 1;
         }
         self.completion_ring.release(offset);
+        self.outstanding = self.outstanding.saturating_sub(offset);
+        offset
+    }
+
+    /// Fills `buffer` with free frames to build new packets in.
+    ///
+    /// Each descriptor points past the frame headroom and its `length` is the
+    /// full writable frame size. Shrink `length` to the packet size before
+    /// passing the descriptor to [`TxSocket::write`]. Returns the number of
+    /// descriptors filled, which is less than `buffer.len()` when the pool
+    /// runs dry.
+    pub fn allocate(&mut self, buffer: &mut [Descriptor]) -> u32 {
+        let config = self.socket.inner.umem.config();
+        let mut offset: u32 = 0;
+        for descriptor in buffer.iter_mut() {
+            let Some(address) = self.frame_allocator.allocate() else {
+                break;
+            };
+            descriptor.address = address + config.frame_headroom as u64;
+            descriptor.length = config.frame_size;
+            offset += 1;
+        }
+        offset
+    }
+
+    /// Takes a free frame to build a packet in. The frame's length is the full
+    /// writable frame size; shrink it with [`Frame::set_len`].
+    ///
+    /// A [`Frame`] keeps a handle to its pool, so this needs
+    /// [`FrameAllocation::Shared`]. Returns `None` when the pool is empty or
+    /// owned by this socket alone.
+    ///
+    /// # Panics
+    ///
+    /// The function panics when `umem` is not the UMEM this socket was built
+    /// on.
+    pub fn allocate_frame<'a>(&mut self, umem: &'a Umem) -> Option<Frame<'a>> {
+        assert!(
+            umem.ptr_eq(&self.socket.inner.umem),
+            "The Umem does not belong to this socket"
+        );
+        let frame_allocator = self.frame_allocator.share()?;
+        let mut descriptor = Descriptor::default();
+        if self.allocate(std::slice::from_mut(&mut descriptor)) == 0 {
+            return None;
+        }
+
+        Some(Frame::new(descriptor, umem, frame_allocator))
+    }
+
+    /// Takes as many free frames as `data` needs, copies `data` into them and
+    /// chains them with [`Frame::set_continued`]. Pass the frames to
+    /// [`TxSocket::write_frames`] to send them as one packet.
+    ///
+    /// Packets larger than a frame need a socket built with
+    /// [`SocketBuilder::use_multi_buffer`]. Returns `None` when the pool
+    /// cannot hold the packet, in which case no frame is taken.
+    ///
+    /// # Panics
+    ///
+    /// The function panics when `umem` is not the UMEM this socket was built
+    /// on.
+    pub fn allocate_packet<'a>(&mut self, umem: &'a Umem, data: &[u8]) -> Option<Vec<Frame<'a>>> {
+        let frame_size = umem.config().frame_size as usize;
+        let mut frames = Vec::with_capacity(data.len().div_ceil(frame_size).max(1));
+        for chunk in data.chunks(frame_size) {
+            // Frames already taken go back to the pool when `frames` drops.
+            let mut frame = self.allocate_frame(umem)?;
+            frame.set_len(chunk.len() as u32);
+            frame.copy_from_slice(chunk);
+            frame.set_continued(true);
+            frames.push(frame);
+        }
+        match frames.last_mut() {
+            Some(frame) => frame.set_continued(false),
+            None => {
+                let mut frame = self.allocate_frame(umem)?;
+                frame.set_len(0);
+                frames.push(frame);
+            }
+        }
+        Some(frames)
+    }
+
+    /// Returns unsent frames from [`TxSocket::allocate`] to the pool.
+    pub fn free(&mut self, buffer: &[Descriptor]) {
+        for descriptor in buffer {
+            self.frame_allocator.free(descriptor.address);
+        }
+    }
+
+    #[inline]
+    pub fn frame_allocator(&self) -> &FrameAllocator {
+        &self.frame_allocator
+    }
+
+    #[inline]
+    pub fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Reads the kernel's `XDP_STATISTICS` for this socket.
+    pub fn stats(&self) -> Result<XdpStatistics, StatsError> {
+        XdpStatistics::read(&self.socket)
+    }
+
+    #[inline]
+    pub fn counters(&self) -> &SocketCounters {
+        &self.counters
     }
 }
 
 pub struct RxSocket {
+    // Dropped before `frame_allocator`, see `TxSocket`.
     socket: Socket,
     ring_size: u32,
+    use_need_wakeup: bool,
     fill_ring: Producer,
     rx_ring: Consumer,
-    descriptor_reader: Receiver<u64>,
+    frame_allocator: FrameAllocator,
+    fill_buffer: Vec<u64>,
+    counters: SocketCounters,
 }
 
 impl RxSocket {
@@ -255,39 +762,238 @@ impl RxSocket {
         let size = self.ring_size.min(buffer.len() as u32);
         self.fill(size);
         self.poll();
-        let (availble, index) = self.rx_ring.peek(size);
+        self.receive(buffer, size)
+    }
+
+    /// Like [`RxSocket::read`], but appends up to `size` owned frames to
+    /// `frames`. Each frame goes back to the pool when dropped.
+    ///
+    /// # Panics
+    ///
+    /// The function panics when `umem` is not the UMEM this socket was built
+    /// on, or when the socket was built with [`FrameAllocation::Split`],
+    /// whose pools cannot be handed to a [`Frame`].
+    #[inline]
+    pub fn read_frames<'a>(
+        &mut self,
+        umem: &'a Umem,
+        frames: &mut Vec<Frame<'a>>,
+        size: u32,
+    ) -> u32 {
+        assert!(
+            umem.ptr_eq(&self.socket.inner.umem),
+            "The Umem does not belong to this socket"
+        );
+        assert!(
+            self.frame_allocator.is_shared(),
+            "Frames need a shared frame allocator"
+        );
+        let size = self.ring_size.min(size);
+        self.fill(size);
+        self.poll();
+
+        let frame_allocator = &self.frame_allocator;
+        let (rx_ring, counters) = (&self.rx_ring, &mut self.counters);
+        Self::receive_with(rx_ring, counters, size, |_, descriptor| {
+            frames.push(Frame::new(
+                descriptor,
+                umem,
+                frame_allocator.share().unwrap(),
+            ));
+        })
+    }
+
+    /// Waits up to `timeout` for packets to arrive.
+    ///
+    /// Returns the number of descriptors written to `buffer`, which is only
+    /// zero on success when `buffer` is empty. Returns
+    /// [`ReadError::TimedOut`] when nothing arrived in time.
+    pub fn read_timeout(
+        &mut self,
+        buffer: &mut [Descriptor],
+        timeout: Duration,
+    ) -> Result<u32, ReadError> {
+        self.wait_and_receive(buffer, Some(Instant::now() + timeout))
+    }
+
+    /// Waits until packets arrive. Unlike [`RxSocket::read`], the thread
+    /// sleeps in `poll` instead of spinning.
+    pub fn read_blocking(&mut self, buffer: &mut [Descriptor]) -> Result<u32, ReadError> {
+        self.wait_and_receive(buffer, None)
+    }
+
+    fn wait_and_receive(
+        &mut self,
+        buffer: &mut [Descriptor],
+        deadline: Option<Instant>,
+    ) -> Result<u32, ReadError> {
+        let size = self.ring_size.min(buffer.len() as u32);
+        // Nothing fits, so waiting would never end with a packet.
+        if size == 0 {
+            return Ok(0);
+        }
+        loop {
+            self.fill(size);
+            let received = self.receive(buffer, size);
+            if received > 0 {
+                return Ok(received);
+            }
+
+            // A negative timeout blocks indefinitely. Round up to whole
+            // milliseconds so that short timeouts do not turn into a spin.
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(ReadError::TimedOut);
+                    }
+                    remaining
+                        .as_micros()
+                        .div_ceil(1000)
+                        .try_into()
+                        .unwrap_or(i32::MAX)
+                }
+                None => -1,
+            };
+
+            let mut poll_fd_struct = pollfd {
+                fd: self.socket.socket_fd(),
+                events: POLLIN,
+                revents: 0,
+            };
+            self.counters.record_wakeup();
+            let value = unsafe { poll(&mut poll_fd_struct, 1, timeout) };
+            if value.is_negative() {
+                let error = std::io::Error::last_os_error();
+                if error.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(ReadError::Poll(error));
+            }
+            if poll_fd_struct.revents & POLLERR != 0 {
+                return Err(ReadError::SocketError);
+            }
+            if poll_fd_struct.revents & POLLHUP != 0 {
+                return Err(ReadError::HangUp);
+            }
+        }
+    }
+
+    #[inline]
+    fn receive(&mut self, buffer: &mut [Descriptor], size: u32) -> u32 {
+        Self::receive_with(
+            &self.rx_ring,
+            &mut self.counters,
+            size,
+            |offset, descriptor| {
+                buffer[offset as usize] = descriptor;
+            },
+        )
+    }
+
+    #[inline]
+    fn receive_with(
+        rx_ring: &Consumer,
+        counters: &mut SocketCounters,
+        size: u32,
+        mut f: impl FnMut(u32, Descriptor),
+    ) -> u32 {
+        let (peeked, index) = rx_ring.peek(size);
+        // The kernel puts whole packets into the ring, so a chained packet is
+        // only cut off by `size`. Leave it for the next call unless it is the
+        // only packet in the batch.
+        let continued =
+            |offset: u32| rx_ring.descriptor(index + offset).options & XDP_PKT_CONTD != 0;
+        let mut available = peeked;
+        while available > 0 && continued(available - 1) {
+            available -= 1;
+        }
+        if available == 0 {
+            available = peeked;
+        }
+        rx_ring.cancel(peeked - available);
+
         let mut offset: u32 = 0;
-        while offset < availble {
-            let descriptor = self.rx_ring.descriptor(index + offset);
-            buffer[offset as usize].address = descriptor.addr;
-            buffer[offset as usize].length = descriptor.len;
+        let mut bytes: u64 = 0;
+        while offset < available {
+            let descriptor = rx_ring.descriptor(index + offset);
+            bytes += descriptor.len as u64;
+            f(
+                offset,
+                Descriptor {
+                    address: descriptor.addr,
+                    length: descriptor.len,
+                    drop: false,
+                    continued: descriptor.options & XDP_PKT_CONTD != 0,
+                },
+            );
             offset += 1;
         }
-        self.rx_ring.release(offset);
+        rx_ring.release(offset);
+        counters.record_batch(offset, bytes);
         offset
     }
 
+    /// Gives free frames to the kernel through the fill ring.
+    ///
+    /// Frames are taken out of the pool before reserving, because reserved
+    /// slots cannot be handed back and must all be submitted.
     #[inline]
     fn fill(&mut self, size: u32) {
-        let (available, index) = self.fill_ring.reserve(size);
+        while self.fill_buffer.len() < size as usize {
+            match self.frame_allocator.allocate() {
+                Some(address) => self.fill_buffer.push(address),
+                None => break,
+            }
+        }
+
+        let (available, index) = self.fill_ring.reserve(self.fill_buffer.len() as u32);
         let mut offset: u32 = 0;
         while offset < available {
-            let descriptor_address = match self.descriptor_reader.try_recv() {
-                Err(TryRecvError::Empty) => break,
-                Err(TryRecvError::Disconnected) => {
-                    panic!("{:?}", "Descriptor receiver disconnected. This is a bug.")
-                }
-                Ok(address) => address,
-            };
             let descriptor = self.fill_ring.fill_address(index + offset);
-            *descriptor = descriptor_address;
+            *descriptor = self.fill_buffer.pop().unwrap();
             offset += 1;
         }
         self.fill_ring.submit(offset);
     }
 
+    /// Returns frames received through [`RxSocket::read`] to the pool once
+    /// the application is done with them.
+    pub fn recycle(&mut self, buffer: &[Descriptor]) {
+        for descriptor in buffer {
+            self.frame_allocator.free(descriptor.address);
+        }
+    }
+
+    #[inline]
+    pub fn frame_allocator(&self) -> &FrameAllocator {
+        &self.frame_allocator
+    }
+
+    #[inline]
+    pub fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Reads the kernel's `XDP_STATISTICS` for this socket.
+    pub fn stats(&self) -> Result<XdpStatistics, StatsError> {
+        XdpStatistics::read(&self.socket)
+    }
+
+    #[inline]
+    pub fn counters(&self) -> &SocketCounters {
+        &self.counters
+    }
+
+    /// Wakes the kernel up to refill the RX ring. With need_wakeup enabled,
+    /// the syscall is skipped unless the kernel has flagged the fill ring.
     #[inline]
     fn poll(&mut self) {
+        if self.use_need_wakeup && !self.fill_ring.needs_wakeup() {
+            return;
+        }
+
+        self.counters.record_wakeup();
         let mut poll_fd_struct = pollfd {
             fd: self.socket.socket_fd(),
             events: POLLIN,
@@ -307,10 +1013,54 @@ pub enum SocketError {
     Umem(#[from] UmemError),
     #[error("Interface name contains null character(s): {0}")]
     InvalidInterfaceName(NulError),
+    #[error("Network interface not found: {0}")]
+    InterfaceNotFound(String),
     #[error("Failed to initialize socket: {0}")]
     Initialize(std::io::Error),
+    #[error("The driver does not support zero-copy mode: {0}")]
+    ZeroCopyUnsupported(std::io::Error),
+    #[error("Failed to read the bind mode with XDP_OPTIONS: {0}")]
+    BindMode(std::io::Error),
     #[error("Socket returned Null. This is a bug.")]
     SocketIsNull,
     #[error("Failed to set RLIMIT_MEMLOCK (try running as root): {0}")]
     Setrlimit(std::io::Error),
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadError {
+    #[error("Timed out waiting for packets")]
+    TimedOut,
+    #[error("Failed to poll socket: {0}")]
+    Poll(std::io::Error),
+    #[error("Socket reported an error (POLLERR)")]
+    SocketError,
+    #[error("Socket hung up (POLLHUP)")]
+    HangUp,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_into_empty_buffer() {
+        // Sockets need AF_XDP and `CAP_NET_RAW`. No program is loaded, so lo
+        // is left as it is.
+        let builder = SocketBuilder {
+            inhibit_program_load: true,
+            ..SocketBuilder::default()
+        };
+        let Ok((_tx_socket, mut rx_socket, _umem)) = builder.build("lo", 0) else {
+            return;
+        };
+
+        assert_eq!(rx_socket.read_blocking(&mut []).unwrap(), 0);
+        assert_eq!(
+            rx_socket
+                .read_timeout(&mut [], Duration::from_secs(1))
+                .unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use crate::socket::Socket;
+use libc::{SOL_XDP, getsockopt, socklen_t};
+use mangonel_libxdp_sys::{XDP_STATISTICS, xdp_statistics};
+use std::mem::MaybeUninit;
+
+/// Kernel counters of an XDP socket, read with `XDP_STATISTICS`.
+///
+/// They cover the whole socket, so [`TxSocket::stats`] and
+/// [`RxSocket::stats`] report the same values.
+///
+/// [`TxSocket::stats`]: crate::TxSocket::stats
+/// [`RxSocket::stats`]: crate::RxSocket::stats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct XdpStatistics {
+    /// Packets dropped by the kernel for reasons other than invalid
+    /// descriptors.
+    pub rx_dropped: u64,
+    /// Packets dropped because of invalid RX descriptors.
+    pub rx_invalid_descs: u64,
+    /// Packets dropped because of invalid TX descriptors.
+    pub tx_invalid_descs: u64,
+    /// Packets dropped because the RX ring was full.
+    pub rx_ring_full: u64,
+    /// Times the kernel found the fill ring empty.
+    pub fill_ring_empty_descs: u64,
+    /// Times the kernel found the TX ring empty.
+    pub tx_ring_empty_descs: u64,
+}
+
+impl XdpStatistics {
+    pub(crate) fn read(socket: &Socket) -> Result<Self, StatsError> {
+        let mut statistics = MaybeUninit::<xdp_statistics>::zeroed();
+        let mut length = size_of::<xdp_statistics>() as socklen_t;
+        let value = unsafe {
+            getsockopt(
+                socket.socket_fd(),
+                SOL_XDP,
+                XDP_STATISTICS as i32,
+                statistics.as_mut_ptr() as *mut _,
+                &mut length,
+            )
+        };
+        if value.is_negative() {
+            return Err(StatsError::Getsockopt(std::io::Error::last_os_error()));
+        }
+
+        // Older kernels fill in only a prefix of the struct and leave the rest
+        // zeroed.
+        let statistics = unsafe { statistics.assume_init() };
+        Ok(Self {
+            rx_dropped: statistics.rx_dropped,
+            rx_invalid_descs: statistics.rx_invalid_descs,
+            tx_invalid_descs: statistics.tx_invalid_descs,
+            rx_ring_full: statistics.rx_ring_full,
+            fill_ring_empty_descs: statistics.rx_fill_ring_empty_descs,
+            tx_ring_empty_descs: statistics.tx_ring_empty_descs,
+        })
+    }
+}
+
+/// Software counters kept by a [`TxSocket`] or [`RxSocket`].
+///
+/// [`TxSocket`]: crate::TxSocket
+/// [`RxSocket`]: crate::RxSocket
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SocketCounters {
+    /// Packets sent or received.
+    pub packets: u64,
+    /// Bytes sent or received.
+    pub bytes: u64,
+    /// Non-empty batches. `packets / batches` is the average batch size.
+    pub batches: u64,
+    /// Largest batch seen.
+    pub max_batch_size: u32,
+    /// Syscalls issued to wake the kernel up.
+    pub wakeups: u64,
+    /// Completed frames that the pool of the socket does not own. They are
+    /// left alone instead of being freed.
+    pub foreign_completions: u64,
+}
+
+impl SocketCounters {
+    #[inline]
+    pub(crate) fn record_batch(&mut self, packets: u32, bytes: u64) {
+        if packets == 0 {
+            return;
+        }
+        self.packets += packets as u64;
+        self.bytes += bytes;
+        self.batches += 1;
+        self.max_batch_size = self.max_batch_size.max(packets);
+    }
+
+    #[inline]
+    pub(crate) fn record_wakeup(&mut self) {
+        self.wakeups += 1;
+    }
+
+    #[inline]
+    pub(crate) fn record_foreign_completion(&mut self) {
+        self.foreign_completions += 1;
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatsError {
+    #[error("Failed to read XDP_STATISTICS: {0}")]
+    Getsockopt(std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_batches() {
+        let mut counters = SocketCounters::default();
+        counters.record_batch(0, 0);
+        counters.record_batch(4, 256);
+        counters.record_batch(2, 128);
+        counters.record_wakeup();
+        counters.record_foreign_completion();
+
+        assert_eq!(
+            counters,
+            SocketCounters {
+                packets: 6,
+                bytes: 384,
+                batches: 2,
+                max_batch_size: 4,
+                wakeups: 1,
+                foreign_completions: 1,
+            }
+        );
+    }
+}
@@ -8,7 +8,7 @@ use mangonel_libxdp_sys::{
 use std::{
     ffi::c_void,
     ptr::{NonNull, null_mut},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 #[derive(Debug)]
@@ -21,6 +21,8 @@ struct UmemInner {
     umem: NonNull<xsk_umem>,
     umem_config: xsk_umem_config,
     mmap: Mmap,
+    frame_count: u32,
+    unclaimed_frames: Mutex<Vec<u64>>,
 }
 
 // SAFETY: Umem is sent between threads so that both TxSocket and RxSocket
@@ -28,6 +30,9 @@ struct UmemInner {
 // The mmap pointer is stable for the lifetime of the Umem, and concurrent
 // reads into non-overlapping frame regions are safe.
 unsafe impl Send for Umem {}
+// SAFETY: Every method takes &self and either reads immutable state or goes
+// through the Mutex. Frames borrow the Umem from whichever thread owns them.
+unsafe impl Sync for Umem {}
 
 impl Drop for UmemInner {
     /// # Panics
@@ -60,6 +65,7 @@ impl Umem {
         mmap: Mmap,
         frame_size: u32,
         frame_headroom_size: u32,
+        frame_count: u32,
         ring_size: u32,
     ) -> Result<(Self, Producer, Consumer), UmemError> {
         let mut umem_ptr = null_mut::<xsk_umem>();
@@ -77,7 +83,9 @@ impl Umem {
             xsk_umem__create(
                 &mut umem_ptr,
                 mmap.as_ptr(),
-                mmap.length().try_into().unwrap(),
+                // The mapping is rounded up to the page size, but the kernel
+                // wants a multiple of the frame size.
+                frame_count as u64 * (frame_size + frame_headroom_size) as u64,
                 fill_ring.as_ptr(),
                 completion_ring.as_ptr(),
                 &umem_config,
@@ -89,11 +97,22 @@ impl Umem {
             )));
         }
 
+        // Every frame starts out unclaimed. Frames are handed to sockets with
+        // `claim_frames()` so that sockets sharing this UMEM never use the
+        // same frame. The list is reversed so that claims start at frame 0.
+        let frame_stride = (frame_size + frame_headroom_size) as u64;
+        let unclaimed_frames = (0..frame_count as u64)
+            .rev()
+            .map(|frame_index| frame_index * frame_stride)
+            .collect();
+
         let umem = Self {
             inner: UmemInner {
                 umem: NonNull::new(umem_ptr).ok_or(UmemError::UmemIsNull)?,
                 umem_config,
                 mmap,
+                frame_count,
+                unclaimed_frames: Mutex::new(unclaimed_frames),
             }
             .into(),
         };
@@ -115,6 +134,85 @@ impl Umem {
     pub fn get_data(&self, address: u64) -> *mut c_void {
         unsafe { xsk_umem__get_data(self.inner.mmap.as_ptr(), address) }
     }
+
+    /// Distance in bytes between the starts of two adjacent frames.
+    #[inline]
+    pub fn frame_stride(&self) -> u64 {
+        (self.inner.umem_config.frame_size + self.inner.umem_config.frame_headroom) as u64
+    }
+
+    /// Returns `true` when both handles refer to the same UMEM.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Umem) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Total number of frames in the UMEM.
+    #[inline]
+    pub fn frame_count(&self) -> u32 {
+        self.inner.frame_count
+    }
+
+    /// Number of frames not yet claimed by any socket.
+    pub fn unclaimed_frame_count(&self) -> u32 {
+        self.inner.unclaimed_frames.lock().unwrap().len() as u32
+    }
+
+    /// Takes `count` frame addresses out of the UMEM for exclusive use by a
+    /// socket. Claimed frames are never handed out again until the
+    /// [`FrameClaim`] is dropped.
+    pub(crate) fn claim_frames(&self, count: u32) -> Result<FrameClaim, UmemError> {
+        let mut unclaimed_frames = self.inner.unclaimed_frames.lock().unwrap();
+        let available = unclaimed_frames.len() as u32;
+        if available < count {
+            return Err(UmemError::InsufficientFrames {
+                requested: count,
+                available,
+            });
+        }
+
+        let split_index = (available - count) as usize;
+        Ok(FrameClaim {
+            umem: self.clone(),
+            frames: unclaimed_frames
+                .split_off(split_index)
+                .into_iter()
+                .rev()
+                .collect(),
+        })
+    }
+}
+
+/// Frames taken out of a [`Umem`] with [`Umem::claim_frames`].
+///
+/// The frames go back to the UMEM when the claim is dropped. The
+/// [`FrameAllocator`]s of a socket hold the claim, so that happens once the
+/// socket and every [`Frame`] drawn from it are gone.
+///
+/// [`FrameAllocator`]: crate::FrameAllocator
+/// [`Frame`]: crate::Frame
+#[derive(Debug)]
+pub(crate) struct FrameClaim {
+    umem: Umem,
+    frames: Vec<u64>,
+}
+
+impl Drop for FrameClaim {
+    fn drop(&mut self) {
+        self.umem
+            .inner
+            .unclaimed_frames
+            .lock()
+            .unwrap()
+            .extend(self.frames.drain(..));
+    }
+}
+
+impl FrameClaim {
+    #[inline]
+    pub(crate) fn frames(&self) -> &[u64] {
+        &self.frames
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -125,6 +223,8 @@ pub enum UmemError {
     UmemIsNull,
     #[error("Failed to free Umem: {0}")]
     Free(std::io::Error),
+    #[error("Requested {requested} frames but only {available} are unclaimed in the Umem")]
+    InsufficientFrames { requested: u32, available: u32 },
     #[error(transparent)]
     Ring(#[from] RingError),
 }
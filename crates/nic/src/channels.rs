@@ -0,0 +1,170 @@
+use crate::ethtool::{ETHTOOL_MSG_CHANNELS_GET, ETHTOOL_MSG_CHANNELS_SET, Ethtool};
+use mangonel_util::netlink::{AttributeWriter, Attributes, NetlinkError};
+
+const ETHTOOL_A_CHANNELS_RX_MAX: u16 = 2;
+const ETHTOOL_A_CHANNELS_TX_MAX: u16 = 3;
+const ETHTOOL_A_CHANNELS_OTHER_MAX: u16 = 4;
+const ETHTOOL_A_CHANNELS_COMBINED_MAX: u16 = 5;
+const ETHTOOL_A_CHANNELS_RX_COUNT: u16 = 6;
+const ETHTOOL_A_CHANNELS_TX_COUNT: u16 = 7;
+const ETHTOOL_A_CHANNELS_OTHER_COUNT: u16 = 8;
+const ETHTOOL_A_CHANNELS_COMBINED_COUNT: u16 = 9;
+
+/// Channel counts of an interface, like `ethtool -l` shows them.
+///
+/// Drivers either report combined channels, which serve an RX and a TX
+/// queue each, or separate RX and TX channels, or a mix. A maximum of 0
+/// means that the driver has no channels of that kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Channels {
+    pub rx_count: u32,
+    pub tx_count: u32,
+    /// Channels for link interrupts and the like, without queues.
+    pub other_count: u32,
+    pub combined_count: u32,
+    pub rx_max: u32,
+    pub tx_max: u32,
+    pub other_max: u32,
+    pub combined_max: u32,
+}
+
+impl Channels {
+    /// Number of queues that an AF_XDP socket can be bound to, i.e. queue
+    /// IDs `0..queue_count()`.
+    #[inline]
+    pub fn queue_count(&self) -> u32 {
+        self.combined_count + self.rx_count.max(self.tx_count)
+    }
+
+    fn parse(attributes: &[u8]) -> Result<Self, NetlinkError> {
+        let mut channels = Self::default();
+        for attribute in Attributes(attributes) {
+            let attribute = attribute?;
+            let field = match attribute.kind {
+                ETHTOOL_A_CHANNELS_RX_MAX => &mut channels.rx_max,
+                ETHTOOL_A_CHANNELS_TX_MAX => &mut channels.tx_max,
+                ETHTOOL_A_CHANNELS_OTHER_MAX => &mut channels.other_max,
+                ETHTOOL_A_CHANNELS_COMBINED_MAX => &mut channels.combined_max,
+                ETHTOOL_A_CHANNELS_RX_COUNT => &mut channels.rx_count,
+                ETHTOOL_A_CHANNELS_TX_COUNT => &mut channels.tx_count,
+                ETHTOOL_A_CHANNELS_OTHER_COUNT => &mut channels.other_count,
+                ETHTOOL_A_CHANNELS_COMBINED_COUNT => &mut channels.combined_count,
+                _ => continue,
+            };
+            *field = attribute.as_u32()?;
+        }
+        Ok(channels)
+    }
+}
+
+/// Channel counts to change. The counts that are `None` stay as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelsUpdate {
+    pub rx_count: Option<u32>,
+    pub tx_count: Option<u32>,
+    pub other_count: Option<u32>,
+    pub combined_count: Option<u32>,
+}
+
+impl ChannelsUpdate {
+    fn write(&self, attributes: &mut AttributeWriter) {
+        for (kind, count) in [
+            (ETHTOOL_A_CHANNELS_RX_COUNT, self.rx_count),
+            (ETHTOOL_A_CHANNELS_TX_COUNT, self.tx_count),
+            (ETHTOOL_A_CHANNELS_OTHER_COUNT, self.other_count),
+            (ETHTOOL_A_CHANNELS_COMBINED_COUNT, self.combined_count),
+        ] {
+            if let Some(count) = count {
+                attributes.put_u32(kind, count);
+            }
+        }
+    }
+}
+
+pub(crate) fn get_channels(interface_index: u32) -> Result<Channels, NetlinkError> {
+    let reply = Ethtool::connect()?.get(ETHTOOL_MSG_CHANNELS_GET, interface_index)?;
+    Channels::parse(&reply)
+}
+
+pub(crate) fn set_channels(
+    interface_index: u32,
+    update: &ChannelsUpdate,
+) -> Result<(), NetlinkError> {
+    Ethtool::connect()?.set(ETHTOOL_MSG_CHANNELS_SET, interface_index, |attributes| {
+        update.write(attributes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Attributes of an ETHTOOL_MSG_CHANNELS_GET_REPLY for eth0 with 8 of
+    /// 63 combined channels and 1 other channel, recorded on x86_64.
+    const COMBINED_REPLY: [u8; 88] = [
+        0x18, 0x00, 0x01, 0x80, 0x08, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x09, 0x00, 0x02,
+        0x00, 0x65, 0x74, 0x68, 0x30, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x08, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x04, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x08, 0x00, 0x05, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x08, 0x00, 0x06, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x08,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x09, 0x00, 0x08, 0x00, 0x00, 0x00,
+    ];
+
+    /// A reply from a driver with 4 RX and 2 TX channels and no combined
+    /// channels, recorded on x86_64, without the header.
+    const SEPARATE_REPLY: [u8; 64] = [
+        0x08, 0x00, 0x02, 0x00, 0x10, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00,
+        0x00, 0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x05, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x08, 0x00, 0x06, 0x00, 0x04, 0x00, 0x00, 0x00, 0x08, 0x00, 0x07, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x08, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x09, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn combined_channels() {
+        let channels = Channels::parse(&COMBINED_REPLY).unwrap();
+        assert_eq!(
+            channels,
+            Channels {
+                other_count: 1,
+                combined_count: 8,
+                other_max: 1,
+                combined_max: 63,
+                ..Default::default()
+            }
+        );
+        assert_eq!(channels.queue_count(), 8);
+
+        // A truncated attribute.
+        assert!(matches!(
+            Channels::parse(&COMBINED_REPLY[..86]),
+            Err(NetlinkError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn separate_channels() {
+        let channels = Channels::parse(&SEPARATE_REPLY).unwrap();
+        assert_eq!(channels.rx_count, 4);
+        assert_eq!(channels.tx_count, 2);
+        assert_eq!(channels.rx_max, 16);
+        assert_eq!(channels.queue_count(), 4);
+    }
+
+    #[test]
+    fn update() {
+        let mut attributes = AttributeWriter::default();
+        ChannelsUpdate {
+            combined_count: Some(4),
+            ..Default::default()
+        }
+        .write(&mut attributes);
+
+        let parsed: Vec<_> = Attributes(attributes.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].kind, ETHTOOL_A_CHANNELS_COMBINED_COUNT);
+        assert_eq!(parsed[0].as_u32().unwrap(), 4);
+    }
+}
@@ -0,0 +1,68 @@
+//! Requests to the ethtool generic netlink family (Linux 5.6 and newer).
+
+use mangonel_util::netlink::{AttributeWriter, GenericNetlink, NetlinkError};
+
+const ETHTOOL_GENL_NAME: &str = "ethtool";
+const ETHTOOL_GENL_VERSION: u8 = 1;
+
+/// Every request and reply starts with this nested attribute.
+const ETHTOOL_A_HEADER: u16 = 1;
+const ETHTOOL_A_HEADER_DEV_INDEX: u16 = 1;
+
+pub(crate) const ETHTOOL_MSG_FEATURES_GET: u8 = 11;
+pub(crate) const ETHTOOL_MSG_FEATURES_SET: u8 = 12;
+pub(crate) const ETHTOOL_MSG_RINGS_GET: u8 = 15;
+pub(crate) const ETHTOOL_MSG_RINGS_SET: u8 = 16;
+pub(crate) const ETHTOOL_MSG_CHANNELS_GET: u8 = 17;
+pub(crate) const ETHTOOL_MSG_CHANNELS_SET: u8 = 18;
+
+/// A connection to the ethtool netlink family.
+#[derive(Debug)]
+pub(crate) struct Ethtool {
+    netlink: GenericNetlink,
+    family: u16,
+}
+
+impl Ethtool {
+    pub(crate) fn connect() -> Result<Self, NetlinkError> {
+        let mut netlink = GenericNetlink::connect()?;
+        let family = netlink.family_id(ETHTOOL_GENL_NAME)?;
+        Ok(Self { netlink, family })
+    }
+
+    /// Sends a `*_GET` request for the interface and returns the attributes
+    /// of the reply.
+    pub(crate) fn get(
+        &mut self,
+        command: u8,
+        interface_index: u32,
+    ) -> Result<Vec<u8>, NetlinkError> {
+        self.request(command, interface_index, |_| {})
+    }
+
+    /// Sends a `*_SET` request for the interface with the attributes that
+    /// `build` writes after the header.
+    pub(crate) fn set(
+        &mut self,
+        command: u8,
+        interface_index: u32,
+        build: impl FnOnce(&mut AttributeWriter),
+    ) -> Result<(), NetlinkError> {
+        self.request(command, interface_index, build).map(drop)
+    }
+
+    fn request(
+        &mut self,
+        command: u8,
+        interface_index: u32,
+        build: impl FnOnce(&mut AttributeWriter),
+    ) -> Result<Vec<u8>, NetlinkError> {
+        let mut attributes = AttributeWriter::default();
+        attributes.put_nested(ETHTOOL_A_HEADER, |header| {
+            header.put_u32(ETHTOOL_A_HEADER_DEV_INDEX, interface_index);
+        });
+        build(&mut attributes);
+        self.netlink
+            .request(self.family, command, ETHTOOL_GENL_VERSION, attributes)
+    }
+}
@@ -0,0 +1,235 @@
+use crate::ethtool::{ETHTOOL_MSG_FEATURES_GET, ETHTOOL_MSG_FEATURES_SET, Ethtool};
+use mangonel_util::netlink::{AttributeWriter, Attributes, NetlinkError};
+
+const ETHTOOL_A_FEATURES_HW: u16 = 2;
+const ETHTOOL_A_FEATURES_WANTED: u16 = 3;
+const ETHTOOL_A_FEATURES_ACTIVE: u16 = 4;
+const ETHTOOL_A_FEATURES_NOCHANGE: u16 = 5;
+
+const ETHTOOL_A_BITSET_NOMASK: u16 = 1;
+const ETHTOOL_A_BITSET_BITS: u16 = 3;
+const ETHTOOL_A_BITSET_BITS_BIT: u16 = 1;
+const ETHTOOL_A_BITSET_BIT_NAME: u16 = 2;
+const ETHTOOL_A_BITSET_BIT_VALUE: u16 = 3;
+
+/// An offload feature, such as `rx-gro`, like `ethtool -k` shows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Feature {
+    /// The kernel name of the feature, e.g. `rx-lro` for large receive
+    /// offload.
+    pub name: String,
+    /// Whether the feature is on.
+    pub active: bool,
+    /// Whether the feature was requested. It can be off despite that when it
+    /// depends on another feature.
+    pub wanted: bool,
+    /// Whether the feature can be turned on or off.
+    pub changeable: bool,
+}
+
+/// The offload features of an interface.
+///
+/// Features that are off and cannot be changed are left out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Features(pub(crate) Vec<Feature>);
+
+impl Features {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Feature> {
+        self.0.iter()
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&Feature> {
+        self.0.iter().find(|feature| feature.name == name)
+    }
+
+    #[inline]
+    pub fn is_active(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|feature| feature.active)
+    }
+
+    fn parse(attributes: &[u8]) -> Result<Self, NetlinkError> {
+        let (mut hw, mut wanted, mut active, mut nochange) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for attribute in Attributes(attributes) {
+            let attribute = attribute?;
+            let names = match attribute.kind {
+                ETHTOOL_A_FEATURES_HW => &mut hw,
+                ETHTOOL_A_FEATURES_WANTED => &mut wanted,
+                ETHTOOL_A_FEATURES_ACTIVE => &mut active,
+                ETHTOOL_A_FEATURES_NOCHANGE => &mut nochange,
+                _ => continue,
+            };
+            names.extend(
+                parse_bitset(attribute.value)?
+                    .into_iter()
+                    .filter_map(|(name, value)| value.then_some(name)),
+            );
+        }
+
+        let mut features = Vec::<Feature>::new();
+        for &name in hw.iter().chain(&wanted).chain(&active) {
+            if features.iter().any(|feature| feature.name == name) {
+                continue;
+            }
+            features.push(Feature {
+                name: name.to_owned(),
+                active: active.contains(&name),
+                wanted: wanted.contains(&name),
+                changeable: hw.contains(&name) && !nochange.contains(&name),
+            });
+        }
+
+        Ok(Self(features))
+    }
+}
+
+/// Reads the bits of a verbose bitset as names and values.
+///
+/// A bitset with the `NOMASK` flag lists only the bits that are set, and
+/// without values.
+fn parse_bitset(bitset: &[u8]) -> Result<Vec<(&str, bool)>, NetlinkError> {
+    let mut list = false;
+    let mut bits = Vec::new();
+    for attribute in Attributes(bitset) {
+        let attribute = attribute?;
+        match attribute.kind {
+            ETHTOOL_A_BITSET_NOMASK => list = true,
+            ETHTOOL_A_BITSET_BITS => {
+                for bit in attribute.nested() {
+                    let bit = bit?;
+                    if bit.kind != ETHTOOL_A_BITSET_BITS_BIT {
+                        continue;
+                    }
+                    let mut name = None;
+                    let mut value = false;
+                    for attribute in bit.nested() {
+                        let attribute = attribute?;
+                        match attribute.kind {
+                            ETHTOOL_A_BITSET_BIT_NAME => name = Some(attribute.as_str()?),
+                            ETHTOOL_A_BITSET_BIT_VALUE => value = true,
+                            _ => {}
+                        }
+                    }
+                    let name =
+                        name.ok_or(NetlinkError::MissingAttribute("ETHTOOL_A_BITSET_BIT_NAME"))?;
+                    bits.push((name, value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if list {
+        for (_, value) in &mut bits {
+            *value = true;
+        }
+    }
+    Ok(bits)
+}
+
+/// Writes the features to turn on or off as a verbose bitset with a mask,
+/// so that the features that are not listed stay as they are.
+fn write_wanted(attributes: &mut AttributeWriter, features: &[(&str, bool)]) {
+    attributes.put_nested(ETHTOOL_A_FEATURES_WANTED, |bitset| {
+        bitset.put_nested(ETHTOOL_A_BITSET_BITS, |bits| {
+            for &(name, enable) in features {
+                bits.put_nested(ETHTOOL_A_BITSET_BITS_BIT, |bit| {
+                    bit.put_str(ETHTOOL_A_BITSET_BIT_NAME, name);
+                    if enable {
+                        bit.put_flag(ETHTOOL_A_BITSET_BIT_VALUE);
+                    }
+                });
+            }
+        });
+    });
+}
+
+pub(crate) fn get_features(interface_index: u32) -> Result<Features, NetlinkError> {
+    let reply = Ethtool::connect()?.get(ETHTOOL_MSG_FEATURES_GET, interface_index)?;
+    Features::parse(&reply)
+}
+
+pub(crate) fn set_features(
+    interface_index: u32,
+    features: &[(&str, bool)],
+) -> Result<(), NetlinkError> {
+    Ethtool::connect()?.set(ETHTOOL_MSG_FEATURES_SET, interface_index, |attributes| {
+        write_wanted(attributes, features)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a bitset that lists the set bits, like the kernel does for
+    /// feature replies.
+    fn put_list(attributes: &mut AttributeWriter, kind: u16, names: &[&str]) {
+        attributes.put_nested(kind, |bitset| {
+            bitset.put_flag(ETHTOOL_A_BITSET_NOMASK);
+            bitset.put_u32(2, 64);
+            bitset.put_nested(ETHTOOL_A_BITSET_BITS, |bits| {
+                for (index, name) in names.iter().enumerate() {
+                    bits.put_nested(ETHTOOL_A_BITSET_BITS_BIT, |bit| {
+                        bit.put_u32(1, index as u32);
+                        bit.put_str(ETHTOOL_A_BITSET_BIT_NAME, name);
+                    });
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn parse() {
+        let mut attributes = AttributeWriter::default();
+        attributes.put_nested(1, |header| header.put_u32(1, 3));
+        put_list(
+            &mut attributes,
+            ETHTOOL_A_FEATURES_HW,
+            &["rx-gro", "rx-lro", "rx-vlan-hw-parse"],
+        );
+        put_list(
+            &mut attributes,
+            ETHTOOL_A_FEATURES_WANTED,
+            &["rx-gro", "rx-vlan-hw-parse"],
+        );
+        put_list(&mut attributes, ETHTOOL_A_FEATURES_ACTIVE, &["rx-gro"]);
+        put_list(
+            &mut attributes,
+            ETHTOOL_A_FEATURES_NOCHANGE,
+            &["rx-vlan-hw-parse", "tx-lockless"],
+        );
+
+        let features = Features::parse(attributes.as_bytes()).unwrap();
+        assert_eq!(features.iter().count(), 3);
+        assert_eq!(
+            features.get("rx-gro"),
+            Some(&Feature {
+                name: "rx-gro".to_owned(),
+                active: true,
+                wanted: true,
+                changeable: true,
+            })
+        );
+        assert!(!features.is_active("rx-lro"));
+        assert!(features.get("rx-lro").unwrap().changeable);
+        let vlan = features.get("rx-vlan-hw-parse").unwrap();
+        assert!(vlan.wanted && !vlan.active && !vlan.changeable);
+        assert_eq!(features.get("tx-lockless"), None);
+    }
+
+    #[test]
+    fn wanted() {
+        let mut attributes = AttributeWriter::default();
+        write_wanted(&mut attributes, &[("rx-gro", false), ("rx-lro", true)]);
+
+        let wanted = Attributes(attributes.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(wanted.kind, ETHTOOL_A_FEATURES_WANTED);
+        assert_eq!(
+            parse_bitset(wanted.value).unwrap(),
+            [("rx-gro", false), ("rx-lro", true)]
+        );
+    }
+}
@@ -1,8 +1,21 @@
-use default_net::mac::MacAddr;
+mod channels;
+mod ethtool;
+mod features;
+mod link;
+mod profile;
+mod rings;
+
+pub use channels::{Channels, ChannelsUpdate};
+pub use features::{Feature, Features};
+pub use link::{InterfaceFlags, LinkState};
+pub use mangonel_util::netlink::NetlinkError;
+pub use profile::{NicState, XdpProfile};
+pub use rings::{Rings, RingsUpdate};
+
+use default_net::{Interface, mac::MacAddr};
 use std::{
     net::{Ipv4Addr, Ipv6Addr},
-    num::ParseIntError,
-    process::Command,
+    path::PathBuf,
 };
 
 #[derive(Clone)]
@@ -37,23 +50,126 @@ impl NetworkInterface {
         let default_iface =
             default_net::get_default_interface().map_err(Error::DefaultInterface)?;
 
-        let iface = NetworkInterface {
-            name: default_iface.name,
-            index: default_iface.index,
-            mac: default_iface
-                .mac_addr
-                .ok_or(Error::DefaultInterface("No MAC address".to_string()))?,
-            ipv4: default_iface.ipv4.iter().map(|ipv4| ipv4.addr).collect(),
-            ipv6: default_iface.ipv6.iter().map(|ipv6| ipv6.addr).collect(),
-        };
+        if default_iface.mac_addr.is_none() {
+            return Err(Error::DefaultInterface("No MAC address".to_string()));
+        }
+
+        Ok(Self::from(default_iface))
+    }
+
+    /// Get every network interface of the system, including the ones that
+    /// are down.
+    pub fn list_all() -> Vec<Self> {
+        default_net::get_interfaces()
+            .into_iter()
+            .map(Self::from)
+            .collect()
+    }
+
+    /// Get the network interface called `name`, e.g. `eth0`.
+    pub fn by_name(name: &str) -> Result<Self, Error> {
+        Self::list_all()
+            .into_iter()
+            .find(|iface| iface.name == name)
+            .ok_or_else(|| Error::NotFound(name.to_owned()))
+    }
+
+    /// Get the network interface with the kernel interface index `index`.
+    pub fn by_index(index: u32) -> Result<Self, Error> {
+        Self::list_all()
+            .into_iter()
+            .find(|iface| iface.index == index)
+            .ok_or_else(|| Error::NotFound(format!("index {index}")))
+    }
+
+    /// Get the MTU, read from sysfs on every call.
+    pub fn mtu(&self) -> Result<u32, Error> {
+        link::mtu(&self.name)
+    }
+
+    /// Get the operational state, e.g. whether the carrier is up.
+    pub fn link_state(&self) -> Result<LinkState, Error> {
+        link::link_state(&self.name)
+    }
+
+    /// Get the `IFF_*` flags, e.g. whether the interface is administratively
+    /// up or promiscuous.
+    pub fn flags(&self) -> Result<InterfaceFlags, Error> {
+        link::flags(&self.name)
+    }
 
-        Ok(iface)
+    /// Get the name of the kernel driver, e.g. `ixgbe`. Virtual interfaces
+    /// such as `lo` have none.
+    pub fn driver(&self) -> Result<Option<String>, Error> {
+        link::driver(&self.name)
+    }
+
+    /// Get the PCI bus address of the device, e.g. `0000:01:00.0`, if it is
+    /// a PCI device.
+    pub fn pci_address(&self) -> Result<Option<String>, Error> {
+        link::pci_address(&self.name)
+    }
+
+    /// Get the channel counts and their maximums, like `ethtool -l`.
+    ///
+    /// This uses the ethtool netlink interface, Linux 5.6 or newer.
+    pub fn channels(&self) -> Result<Channels, Error> {
+        Ok(channels::get_channels(self.index)?)
+    }
+
+    /// Change some of the channel counts, like `ethtool -L`.
+    ///
+    /// Requires `CAP_NET_ADMIN`.
+    pub fn set_channels(&self, update: ChannelsUpdate) -> Result<(), Error> {
+        Ok(channels::set_channels(self.index, &update)?)
+    }
+
+    /// Get the offload features, like `ethtool -k`.
+    pub fn features(&self) -> Result<Features, Error> {
+        Ok(features::get_features(self.index)?)
+    }
+
+    /// Turn offload features on or off by their kernel names, like
+    /// `ethtool -K`. The features that are not listed stay as they are.
+    ///
+    /// Requires `CAP_NET_ADMIN`.
+    pub fn set_features(&self, features: &[(&str, bool)]) -> Result<(), Error> {
+        Ok(features::set_features(self.index, features)?)
+    }
+
+    /// Get the RX and TX ring sizes and their maximums, like `ethtool -g`.
+    pub fn rings(&self) -> Result<Rings, Error> {
+        Ok(rings::get_rings(self.index)?)
+    }
+
+    /// Change some of the ring sizes, like `ethtool -G`.
+    ///
+    /// Requires `CAP_NET_ADMIN`.
+    pub fn set_rings(&self, update: RingsUpdate) -> Result<(), Error> {
+        Ok(rings::set_rings(self.index, &update)?)
+    }
+
+    /// Apply the offload and ring settings of `profile` before running XDP.
+    ///
+    /// Returns the settings that were changed, to pass to
+    /// [`NetworkInterface::restore`] on exit. Nothing is changed when this
+    /// fails.
+    ///
+    /// Requires `CAP_NET_ADMIN`.
+    pub fn prepare_for_xdp(&self, profile: &XdpProfile) -> Result<NicState, Error> {
+        profile::prepare(self.index, profile)
+    }
+
+    /// Undo the changes of [`NetworkInterface::prepare_for_xdp`].
+    ///
+    /// Requires `CAP_NET_ADMIN`.
+    pub fn restore(&self, state: &NicState) -> Result<(), Error> {
+        profile::restore(self.index, state)
     }
 
     /// Set the number of combined TX/RX queues for this interface
     ///
-    /// This uses ethtool to change the combined channel count.
-    /// Requires appropriate permissions (typically root/sudo).
+    /// Requires `CAP_NET_ADMIN`.
     ///
     /// # Arguments
     /// * `queue_count` - The number of combined queues to set
@@ -62,63 +178,22 @@ impl NetworkInterface {
     /// * `Ok(())` on success
     /// * `Err(Error)` if the operation fails
     pub fn set_queue_count(&self, queue_count: u32) -> Result<(), Error> {
-        let output = Command::new("ethtool")
-            .arg("-L")
-            .arg(&self.name)
-            .arg("combined")
-            .arg(queue_count.to_string())
-            .output()
-            .map_err(|error| Error::Ethtool(error.to_string()))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::Ethtool(stderr.to_string()));
-        }
-
-        Ok(())
+        self.set_channels(ChannelsUpdate {
+            combined_count: Some(queue_count),
+            ..Default::default()
+        })
     }
 
-    /// Get the current number of combined TX/RX queues for this interface
+    /// Get the current number of TX/RX queues for this interface
     ///
-    /// This uses ethtool to query the combined channel count.
+    /// These are the combined channels plus any separate RX or TX
+    /// channels. See [`Channels::queue_count`].
     ///
     /// # Returns
-    /// * `Ok(u32)` with the number of combined queues
+    /// * `Ok(u32)` with the number of queues
     /// * `Err(Error)` if the operation fails
     pub fn get_queue_count(&self) -> Result<u32, Error> {
-        let output = Command::new("ethtool")
-            .arg("-l")
-            .arg(&self.name)
-            .output()
-            .map_err(|error| Error::Ethtool(error.to_string()))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::Ethtool(stderr.to_string()));
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-
-        // Parse the output to find "Combined:" line in the "Current hardware settings:" section
-        let mut in_current_section = false;
-        for line in stdout.lines() {
-            if line.contains("Current hardware settings:") {
-                in_current_section = true;
-                continue;
-            }
-
-            if in_current_section && line.contains("Combined:") {
-                let parts: Vec<&str> = line.split(':').collect();
-                if parts.len() >= 2 {
-                    let count_str = parts[1].trim();
-                    return count_str.parse::<u32>().map_err(Error::ParseError);
-                }
-            }
-        }
-
-        Err(Error::Ethtool(
-            "Could not find Combined queue count in ethtool output".to_owned(),
-        ))
+        Ok(self.channels()?.queue_count())
     }
 
     pub fn name(&self) -> &str {
@@ -142,15 +217,41 @@ impl NetworkInterface {
     }
 }
 
+impl From<Interface> for NetworkInterface {
+    /// Interfaces without a hardware address, such as tunnels, get a zero
+    /// MAC address.
+    fn from(iface: Interface) -> Self {
+        Self {
+            name: iface.name,
+            index: iface.index,
+            mac: iface.mac_addr.unwrap_or_else(MacAddr::zero),
+            ipv4: iface.ipv4.iter().map(|ipv4| ipv4.addr).collect(),
+            ipv6: iface.ipv6.iter().map(|ipv6| ipv6.addr).collect(),
+        }
+    }
+}
+
+/// Lets sockets be built for a [`NetworkInterface`] directly.
+impl AsRef<str> for NetworkInterface {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
 /// Error types for network interface operations
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Failed to get default network interface: {0}")]
     DefaultInterface(String),
+    #[error("Network interface not found: {0}")]
+    NotFound(String),
+    #[error("Failed to read {0}: {1}")]
+    Sysfs(PathBuf, std::io::Error),
+    #[error("Unexpected value in {0}: {1:?}")]
+    InvalidAttribute(PathBuf, String),
     #[error("Ethtool error: {0}")]
-    Ethtool(String),
-    #[error("Failed to parse value: {0}")]
-    ParseError(#[from] ParseIntError),
+    Ethtool(#[from] NetlinkError),
 }
 
 #[cfg(test)]
@@ -185,4 +286,32 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_loopback() {
+        let Ok(lo) = NetworkInterface::by_name("lo") else {
+            println!("No loopback interface");
+            return;
+        };
+        assert_eq!(NetworkInterface::by_index(lo.index()).unwrap().name(), "lo");
+        assert!(
+            NetworkInterface::list_all()
+                .iter()
+                .any(|iface| iface.name() == "lo")
+        );
+        assert!(lo.flags().unwrap().contains(InterfaceFlags::LOOPBACK));
+        assert!(lo.mtu().unwrap() > 0);
+        assert_eq!(lo.driver().unwrap(), None);
+        assert_eq!(lo.pci_address().unwrap(), None);
+        match lo.features() {
+            Ok(features) => assert!(features.get("rx-gro").is_some()),
+            Err(e) => println!("Could not get features: {}", e),
+        }
+        println!("lo: {:?}", lo.link_state().unwrap());
+
+        assert!(matches!(
+            NetworkInterface::by_name("mangonel-none"),
+            Err(Error::NotFound(_))
+        ));
+    }
 }
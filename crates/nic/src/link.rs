@@ -0,0 +1,149 @@
+use crate::Error;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const SYSFS_NET: &str = "/sys/class/net";
+
+/// Operational state of an interface (RFC 2863), as in `ip link`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkState {
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    Dormant,
+    Up,
+}
+
+impl LinkState {
+    fn parse(operstate: &str) -> Option<Self> {
+        let state = match operstate.trim() {
+            "unknown" => Self::Unknown,
+            "notpresent" => Self::NotPresent,
+            "down" => Self::Down,
+            "lowerlayerdown" => Self::LowerLayerDown,
+            "testing" => Self::Testing,
+            "dormant" => Self::Dormant,
+            "up" => Self::Up,
+            _ => return None,
+        };
+        Some(state)
+    }
+}
+
+/// Administrative interface flags (`IFF_*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InterfaceFlags(pub u32);
+
+impl InterfaceFlags {
+    pub const UP: Self = Self(libc::IFF_UP as u32);
+    pub const BROADCAST: Self = Self(libc::IFF_BROADCAST as u32);
+    pub const LOOPBACK: Self = Self(libc::IFF_LOOPBACK as u32);
+    pub const POINTOPOINT: Self = Self(libc::IFF_POINTOPOINT as u32);
+    pub const RUNNING: Self = Self(libc::IFF_RUNNING as u32);
+    pub const NOARP: Self = Self(libc::IFF_NOARP as u32);
+    pub const PROMISC: Self = Self(libc::IFF_PROMISC as u32);
+    pub const ALLMULTI: Self = Self(libc::IFF_ALLMULTI as u32);
+    pub const MULTICAST: Self = Self(libc::IFF_MULTICAST as u32);
+
+    #[inline]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Parses the hexadecimal value of `/sys/class/net/<name>/flags`.
+    fn parse(flags: &str) -> Option<Self> {
+        let flags = flags.trim();
+        let digits = flags.strip_prefix("0x").unwrap_or(flags);
+        u32::from_str_radix(digits, 16).ok().map(Self)
+    }
+}
+
+impl std::ops::BitOr for InterfaceFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+fn attribute_path(interface_name: &str, attribute: &str) -> PathBuf {
+    Path::new(SYSFS_NET).join(interface_name).join(attribute)
+}
+
+fn read_attribute(interface_name: &str, attribute: &str) -> Result<String, Error> {
+    let path = attribute_path(interface_name, attribute);
+    fs::read_to_string(&path).map_err(|error| Error::Sysfs(path, error))
+}
+
+/// The last component of the symlink target, or `None` when the link does
+/// not exist, e.g. for virtual devices.
+fn read_link_name(interface_name: &str, attribute: &str) -> Result<Option<String>, Error> {
+    let path = attribute_path(interface_name, attribute);
+    match fs::read_link(&path) {
+        Ok(target) => Ok(target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(Error::Sysfs(path, error)),
+    }
+}
+
+pub(crate) fn mtu(interface_name: &str) -> Result<u32, Error> {
+    let mtu = read_attribute(interface_name, "mtu")?;
+    mtu.trim()
+        .parse()
+        .map_err(|_| Error::InvalidAttribute(attribute_path(interface_name, "mtu"), mtu))
+}
+
+pub(crate) fn link_state(interface_name: &str) -> Result<LinkState, Error> {
+    let operstate = read_attribute(interface_name, "operstate")?;
+    LinkState::parse(&operstate).ok_or_else(|| {
+        Error::InvalidAttribute(attribute_path(interface_name, "operstate"), operstate)
+    })
+}
+
+pub(crate) fn flags(interface_name: &str) -> Result<InterfaceFlags, Error> {
+    let flags = read_attribute(interface_name, "flags")?;
+    InterfaceFlags::parse(&flags)
+        .ok_or_else(|| Error::InvalidAttribute(attribute_path(interface_name, "flags"), flags))
+}
+
+pub(crate) fn driver(interface_name: &str) -> Result<Option<String>, Error> {
+    read_link_name(interface_name, "device/driver")
+}
+
+pub(crate) fn pci_address(interface_name: &str) -> Result<Option<String>, Error> {
+    if read_link_name(interface_name, "device/subsystem")?.as_deref() != Some("pci") {
+        return Ok(None);
+    }
+    read_link_name(interface_name, "device")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_link_state() {
+        assert_eq!(LinkState::parse("up\n"), Some(LinkState::Up));
+        assert_eq!(
+            LinkState::parse("lowerlayerdown\n"),
+            Some(LinkState::LowerLayerDown)
+        );
+        assert_eq!(LinkState::parse("sideways"), None);
+    }
+
+    #[test]
+    fn parse_flags() {
+        let flags = InterfaceFlags::parse("0x1003\n").unwrap();
+        assert!(flags.contains(InterfaceFlags::UP | InterfaceFlags::BROADCAST));
+        assert!(flags.contains(InterfaceFlags::MULTICAST));
+        assert!(!flags.contains(InterfaceFlags::LOOPBACK));
+        assert_eq!(InterfaceFlags::parse("0xzz"), None);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{
+    Error,
+    features::{self, Features},
+    rings::{self, Rings, RingsUpdate},
+};
+
+/// Receive offloads that merge packets, which XDP programs and AF_XDP
+/// sockets should see one by one.
+const MERGING_OFFLOADS: [&str; 3] = ["rx-gro", "rx-gro-hw", "rx-lro"];
+/// Offloads that strip VLAN tags from received frames.
+const VLAN_STRIPPING: [&str; 2] = ["rx-vlan-hw-parse", "rx-vlan-stag-hw-parse"];
+
+/// NIC settings that [`NetworkInterface::prepare_for_xdp`] applies.
+///
+/// [`NetworkInterface::prepare_for_xdp`]: crate::NetworkInterface::prepare_for_xdp
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XdpProfile {
+    /// Turn off GRO, hardware GRO and LRO.
+    pub disable_merging_offloads: bool,
+    /// Turn off VLAN tag stripping so that received frames keep their tags.
+    pub keep_vlan_tags: bool,
+    /// RX ring size in descriptors, clamped to the maximum of the driver.
+    /// `None` leaves the size as it is.
+    pub rx_ring_size: Option<u32>,
+    /// TX ring size in descriptors, clamped to the maximum of the driver.
+    /// `None` leaves the size as it is.
+    pub tx_ring_size: Option<u32>,
+}
+
+impl Default for XdpProfile {
+    fn default() -> Self {
+        Self {
+            disable_merging_offloads: true,
+            keep_vlan_tags: false,
+            rx_ring_size: None,
+            tx_ring_size: None,
+        }
+    }
+}
+
+impl XdpProfile {
+    /// Features that are on and have to be turned off.
+    fn feature_changes<'a>(&self, features: &'a Features) -> Vec<(&'a str, bool)> {
+        let mut names = Vec::new();
+        if self.disable_merging_offloads {
+            names.extend(MERGING_OFFLOADS);
+        }
+        if self.keep_vlan_tags {
+            names.extend(VLAN_STRIPPING);
+        }
+
+        names
+            .into_iter()
+            .filter_map(|name| features.get(name))
+            .filter(|feature| feature.wanted && feature.changeable)
+            .map(|feature| (feature.name.as_str(), false))
+            .collect()
+    }
+
+    /// Ring sizes that differ from the current ones.
+    fn ring_update(&self, rings: &Rings) -> RingsUpdate {
+        let size = |wanted: Option<u32>, size: u32, max: u32| {
+            wanted
+                .map(|wanted| wanted.min(max))
+                .filter(|&wanted| wanted != size)
+        };
+        RingsUpdate {
+            rx_size: size(self.rx_ring_size, rings.rx_size, rings.rx_max),
+            tx_size: size(self.tx_ring_size, rings.tx_size, rings.tx_max),
+            ..Default::default()
+        }
+    }
+}
+
+/// The NIC settings before [`NetworkInterface::prepare_for_xdp`] changed
+/// them, to restore with [`NetworkInterface::restore`].
+///
+/// [`NetworkInterface::prepare_for_xdp`]: crate::NetworkInterface::prepare_for_xdp
+/// [`NetworkInterface::restore`]: crate::NetworkInterface::restore
+#[must_use]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NicState {
+    features: Vec<(String, bool)>,
+    rings: Option<RingsUpdate>,
+}
+
+impl NicState {
+    /// Whether nothing was changed.
+    #[inline]
+    pub fn is_unchanged(&self) -> bool {
+        self.features.is_empty() && self.rings.is_none()
+    }
+}
+
+pub(crate) fn prepare(interface_index: u32, profile: &XdpProfile) -> Result<NicState, Error> {
+    let mut state = NicState::default();
+
+    let features = features::get_features(interface_index)?;
+    let changes = profile.feature_changes(&features);
+    if !changes.is_empty() {
+        features::set_features(interface_index, &changes)?;
+        state.features = changes
+            .iter()
+            .map(|&(name, enable)| (name.to_owned(), !enable))
+            .collect();
+    }
+
+    if profile.rx_ring_size.is_some() || profile.tx_ring_size.is_some() {
+        let result = rings::get_rings(interface_index).and_then(|rings| {
+            let update = profile.ring_update(&rings);
+            if update != RingsUpdate::default() {
+                rings::set_rings(interface_index, &update)?;
+                state.rings = Some(RingsUpdate {
+                    rx_size: update.rx_size.map(|_| rings.rx_size),
+                    tx_size: update.tx_size.map(|_| rings.tx_size),
+                    ..Default::default()
+                });
+            }
+            Ok(())
+        });
+        if let Err(error) = result {
+            // Leave the NIC as it was. The first error is the one to report.
+            let _ = restore(interface_index, &state);
+            return Err(error.into());
+        }
+    }
+
+    Ok(state)
+}
+
+pub(crate) fn restore(interface_index: u32, state: &NicState) -> Result<(), Error> {
+    if let Some(update) = &state.rings {
+        rings::set_rings(interface_index, update)?;
+    }
+    if !state.features.is_empty() {
+        let features: Vec<_> = state
+            .features
+            .iter()
+            .map(|(name, enable)| (name.as_str(), *enable))
+            .collect();
+        features::set_features(interface_index, &features)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Feature;
+
+    fn feature(name: &str, wanted: bool, changeable: bool) -> Feature {
+        Feature {
+            name: name.to_owned(),
+            active: wanted,
+            wanted,
+            changeable,
+        }
+    }
+
+    #[test]
+    fn feature_changes() {
+        let features = Features(vec![
+            feature("rx-gro", true, true),
+            feature("rx-lro", false, true),
+            feature("rx-gro-hw", true, false),
+            feature("rx-vlan-hw-parse", true, true),
+        ]);
+
+        let profile = XdpProfile::default();
+        assert_eq!(profile.feature_changes(&features), [("rx-gro", false)]);
+
+        let profile = XdpProfile {
+            keep_vlan_tags: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            profile.feature_changes(&features),
+            [("rx-gro", false), ("rx-vlan-hw-parse", false)]
+        );
+    }
+
+    #[test]
+    fn ring_update() {
+        let rings = Rings {
+            rx_size: 512,
+            tx_size: 512,
+            rx_max: 4096,
+            tx_max: 1024,
+            ..Default::default()
+        };
+
+        let profile = XdpProfile {
+            rx_ring_size: Some(8192),
+            tx_ring_size: Some(512),
+            ..Default::default()
+        };
+        assert_eq!(
+            profile.ring_update(&rings),
+            RingsUpdate {
+                rx_size: Some(4096),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            XdpProfile::default().ring_update(&rings),
+            RingsUpdate::default()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::ethtool::{ETHTOOL_MSG_RINGS_GET, ETHTOOL_MSG_RINGS_SET, Ethtool};
+use mangonel_util::netlink::{AttributeWriter, Attributes, NetlinkError};
+
+const ETHTOOL_A_RINGS_RX_MAX: u16 = 2;
+const ETHTOOL_A_RINGS_RX_MINI_MAX: u16 = 3;
+const ETHTOOL_A_RINGS_RX_JUMBO_MAX: u16 = 4;
+const ETHTOOL_A_RINGS_TX_MAX: u16 = 5;
+const ETHTOOL_A_RINGS_RX: u16 = 6;
+const ETHTOOL_A_RINGS_RX_MINI: u16 = 7;
+const ETHTOOL_A_RINGS_RX_JUMBO: u16 = 8;
+const ETHTOOL_A_RINGS_TX: u16 = 9;
+
+/// Ring sizes of an interface in descriptors, like `ethtool -g` shows them.
+///
+/// Most drivers only have the RX and TX rings. A maximum of 0 means that
+/// the driver has no ring of that kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rings {
+    pub rx_size: u32,
+    pub rx_mini_size: u32,
+    pub rx_jumbo_size: u32,
+    pub tx_size: u32,
+    pub rx_max: u32,
+    pub rx_mini_max: u32,
+    pub rx_jumbo_max: u32,
+    pub tx_max: u32,
+}
+
+impl Rings {
+    fn parse(attributes: &[u8]) -> Result<Self, NetlinkError> {
+        let mut rings = Self::default();
+        for attribute in Attributes(attributes) {
+            let attribute = attribute?;
+            let field = match attribute.kind {
+                ETHTOOL_A_RINGS_RX_MAX => &mut rings.rx_max,
+                ETHTOOL_A_RINGS_RX_MINI_MAX => &mut rings.rx_mini_max,
+                ETHTOOL_A_RINGS_RX_JUMBO_MAX => &mut rings.rx_jumbo_max,
+                ETHTOOL_A_RINGS_TX_MAX => &mut rings.tx_max,
+                ETHTOOL_A_RINGS_RX => &mut rings.rx_size,
+                ETHTOOL_A_RINGS_RX_MINI => &mut rings.rx_mini_size,
+                ETHTOOL_A_RINGS_RX_JUMBO => &mut rings.rx_jumbo_size,
+                ETHTOOL_A_RINGS_TX => &mut rings.tx_size,
+                _ => continue,
+            };
+            *field = attribute.as_u32()?;
+        }
+        Ok(rings)
+    }
+}
+
+/// Ring sizes to change. The sizes that are `None` stay as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RingsUpdate {
+    pub rx_size: Option<u32>,
+    pub rx_mini_size: Option<u32>,
+    pub rx_jumbo_size: Option<u32>,
+    pub tx_size: Option<u32>,
+}
+
+impl RingsUpdate {
+    fn write(&self, attributes: &mut AttributeWriter) {
+        for (kind, size) in [
+            (ETHTOOL_A_RINGS_RX, self.rx_size),
+            (ETHTOOL_A_RINGS_RX_MINI, self.rx_mini_size),
+            (ETHTOOL_A_RINGS_RX_JUMBO, self.rx_jumbo_size),
+            (ETHTOOL_A_RINGS_TX, self.tx_size),
+        ] {
+            if let Some(size) = size {
+                attributes.put_u32(kind, size);
+            }
+        }
+    }
+}
+
+pub(crate) fn get_rings(interface_index: u32) -> Result<Rings, NetlinkError> {
+    let reply = Ethtool::connect()?.get(ETHTOOL_MSG_RINGS_GET, interface_index)?;
+    Rings::parse(&reply)
+}
+
+pub(crate) fn set_rings(interface_index: u32, update: &RingsUpdate) -> Result<(), NetlinkError> {
+    Ethtool::connect()?.set(ETHTOOL_MSG_RINGS_SET, interface_index, |attributes| {
+        update.write(attributes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let mut attributes = AttributeWriter::default();
+        attributes.put_nested(1, |header| header.put_u32(1, 3));
+        for (kind, value) in [
+            (ETHTOOL_A_RINGS_RX_MAX, 4096),
+            (ETHTOOL_A_RINGS_TX_MAX, 4096),
+            (ETHTOOL_A_RINGS_RX, 512),
+            (ETHTOOL_A_RINGS_TX, 1024),
+        ] {
+            attributes.put_u32(kind, value);
+        }
+        // The buffer length, which is not a ring size.
+        attributes.put_u32(10, 2048);
+
+        let rings = Rings::parse(attributes.as_bytes()).unwrap();
+        assert_eq!(
+            rings,
+            Rings {
+                rx_size: 512,
+                tx_size: 1024,
+                rx_max: 4096,
+                tx_max: 4096,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn update() {
+        let mut attributes = AttributeWriter::default();
+        RingsUpdate {
+            rx_size: Some(2048),
+            tx_size: Some(1024),
+            ..Default::default()
+        }
+        .write(&mut attributes);
+
+        let parsed: Vec<_> = Attributes(attributes.as_bytes())
+            .map(|attribute| {
+                let attribute = attribute.unwrap();
+                (attribute.kind, attribute.as_u32().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            parsed,
+            [(ETHTOOL_A_RINGS_RX, 2048), (ETHTOOL_A_RINGS_TX, 1024)]
+        );
+    }
+}
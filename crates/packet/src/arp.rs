@@ -0,0 +1,132 @@
+use crate::{PacketError, check_length, ethernet::EtherType, read_u16};
+use std::net::Ipv4Addr;
+
+/// An ARP packet (RFC 826).
+#[derive(Clone, Copy, Debug)]
+pub struct ArpPacket<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> ArpPacket<'a> {
+    /// Length of the fixed part, before the addresses.
+    pub const HEADER_LEN: usize = 8;
+    pub const HARDWARE_TYPE_ETHERNET: u16 = 1;
+    pub const OPERATION_REQUEST: u16 = 1;
+    pub const OPERATION_REPLY: u16 = 2;
+
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("ARP", buffer, Self::HEADER_LEN)?;
+        let packet = Self { buffer };
+        check_length("ARP", buffer, packet.packet_len())?;
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn hardware_type(&self) -> u16 {
+        read_u16(self.buffer, 0)
+    }
+
+    #[inline]
+    pub fn protocol_type(&self) -> EtherType {
+        EtherType(read_u16(self.buffer, 2))
+    }
+
+    #[inline]
+    pub fn hardware_address_len(&self) -> u8 {
+        self.buffer[4]
+    }
+
+    #[inline]
+    pub fn protocol_address_len(&self) -> u8 {
+        self.buffer[5]
+    }
+
+    #[inline]
+    pub fn operation(&self) -> u16 {
+        read_u16(self.buffer, 6)
+    }
+
+    #[inline]
+    pub fn sender_hardware_address(&self) -> &'a [u8] {
+        let start = Self::HEADER_LEN;
+        &self.buffer[start..start + self.hardware_address_len() as usize]
+    }
+
+    #[inline]
+    pub fn sender_protocol_address(&self) -> &'a [u8] {
+        let start = Self::HEADER_LEN + self.hardware_address_len() as usize;
+        &self.buffer[start..start + self.protocol_address_len() as usize]
+    }
+
+    #[inline]
+    pub fn target_hardware_address(&self) -> &'a [u8] {
+        let start = Self::HEADER_LEN
+            + self.hardware_address_len() as usize
+            + self.protocol_address_len() as usize;
+        &self.buffer[start..start + self.hardware_address_len() as usize]
+    }
+
+    #[inline]
+    pub fn target_protocol_address(&self) -> &'a [u8] {
+        let start = Self::HEADER_LEN
+            + 2 * self.hardware_address_len() as usize
+            + self.protocol_address_len() as usize;
+        &self.buffer[start..start + self.protocol_address_len() as usize]
+    }
+
+    /// Sender IPv4 address, when this is an IPv4 over Ethernet packet.
+    #[inline]
+    pub fn sender_ipv4(&self) -> Option<Ipv4Addr> {
+        self.is_ipv4_over_ethernet().then(|| {
+            <[u8; 4]>::try_from(self.sender_protocol_address())
+                .unwrap()
+                .into()
+        })
+    }
+
+    /// Target IPv4 address, when this is an IPv4 over Ethernet packet.
+    #[inline]
+    pub fn target_ipv4(&self) -> Option<Ipv4Addr> {
+        self.is_ipv4_over_ethernet().then(|| {
+            <[u8; 4]>::try_from(self.target_protocol_address())
+                .unwrap()
+                .into()
+        })
+    }
+
+    /// Total length of the packet as given by its address lengths.
+    #[inline]
+    pub fn packet_len(&self) -> usize {
+        Self::HEADER_LEN
+            + 2 * (self.hardware_address_len() as usize + self.protocol_address_len() as usize)
+    }
+
+    #[inline]
+    fn is_ipv4_over_ethernet(&self) -> bool {
+        self.hardware_type() == Self::HARDWARE_TYPE_ETHERNET
+            && self.protocol_type() == EtherType::IPV4
+            && self.hardware_address_len() == 6
+            && self.protocol_address_len() == 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request() {
+        let buffer = [
+            0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, // fixed part
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 192, 168, 0, 1, // sender
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 192, 168, 0, 2, // target
+        ];
+        let packet = ArpPacket::new(&buffer).unwrap();
+        assert_eq!(packet.operation(), ArpPacket::OPERATION_REQUEST);
+        assert_eq!(packet.sender_hardware_address(), &buffer[8..14]);
+        assert_eq!(packet.sender_ipv4(), Some(Ipv4Addr::new(192, 168, 0, 1)));
+        assert_eq!(packet.target_ipv4(), Some(Ipv4Addr::new(192, 168, 0, 2)));
+
+        assert!(ArpPacket::new(&buffer[..27]).is_err());
+    }
+}
@@ -0,0 +1,609 @@
+use crate::{
+    EtherType, EthernetFrame, IcmpPacket, IpProtocol, Ipv4Packet, Ipv6Packet, PacketError,
+    TcpFlags, TcpSegment, UdpDatagram, VlanTag, checksum, write_u16, write_u32,
+};
+#[cfg(feature = "libxdp")]
+use mangonel_libxdp::Frame;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Writes a packet from its headers and payload.
+///
+/// The packet is written straight into the destination buffer, so building
+/// into a UMEM frame needs no copy other than the payload. Length fields and
+/// checksums are filled in. With the `libxdp` feature,
+/// `PacketBuilder::write_frame` writes into a `mangonel_libxdp::Frame`.
+#[derive(Clone, Debug, Default)]
+pub struct PacketBuilder {
+    pub ethernet: EthernetHeader,
+    pub network: Option<NetworkHeader>,
+    /// Requires a network header.
+    pub transport: Option<TransportHeader>,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EthernetHeader {
+    pub destination: [u8; 6],
+    pub source: [u8; 6],
+    /// Tags from the outermost to the innermost, at most two.
+    pub vlan_tags: Vec<VlanTag>,
+    /// EtherType of the payload when there is no network header.
+    pub ether_type: EtherType,
+}
+
+impl Default for EthernetHeader {
+    fn default() -> Self {
+        Self {
+            destination: [0xff; 6],
+            source: [0; 6],
+            vlan_tags: Vec::new(),
+            ether_type: EtherType::IPV4,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum NetworkHeader {
+    Ipv4(Ipv4Header),
+    Ipv6(Ipv6Header),
+}
+
+/// An IPv4 header without options.
+#[derive(Clone, Debug)]
+pub struct Ipv4Header {
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    pub dscp: u8,
+    pub ecn: u8,
+    pub identification: u16,
+    pub dont_fragment: bool,
+    pub ttl: u8,
+}
+
+impl Default for Ipv4Header {
+    fn default() -> Self {
+        Self {
+            source: Ipv4Addr::UNSPECIFIED,
+            destination: Ipv4Addr::UNSPECIFIED,
+            dscp: 0,
+            ecn: 0,
+            identification: 0,
+            dont_fragment: true,
+            ttl: 64,
+        }
+    }
+}
+
+/// An IPv6 header without extension headers.
+#[derive(Clone, Debug)]
+pub struct Ipv6Header {
+    pub source: Ipv6Addr,
+    pub destination: Ipv6Addr,
+    pub traffic_class: u8,
+    /// Only the lower 20 bits are used.
+    pub flow_label: u32,
+    pub hop_limit: u8,
+}
+
+impl Default for Ipv6Header {
+    fn default() -> Self {
+        Self {
+            source: Ipv6Addr::UNSPECIFIED,
+            destination: Ipv6Addr::UNSPECIFIED,
+            traffic_class: 0,
+            flow_label: 0,
+            hop_limit: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum TransportHeader {
+    Udp(UdpHeader),
+    Tcp(TcpHeader),
+    Icmp(IcmpHeader),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UdpHeader {
+    pub source_port: u16,
+    pub destination_port: u16,
+}
+
+/// A TCP header without options.
+#[derive(Clone, Debug)]
+pub struct TcpHeader {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub sequence_number: u32,
+    pub acknowledgment_number: u32,
+    pub flags: TcpFlags,
+    pub window: u16,
+    pub urgent_pointer: u16,
+}
+
+impl Default for TcpHeader {
+    fn default() -> Self {
+        Self {
+            source_port: 0,
+            destination_port: 0,
+            sequence_number: 0,
+            acknowledgment_number: 0,
+            flags: TcpFlags::SYN,
+            window: u16::MAX,
+            urgent_pointer: 0,
+        }
+    }
+}
+
+/// An ICMPv4 or ICMPv6 header, depending on the network header it is
+/// carried in.
+#[derive(Clone, Debug, Default)]
+pub struct IcmpHeader {
+    pub message_type: u8,
+    pub code: u8,
+    pub rest_of_header: [u8; 4],
+}
+
+impl IcmpHeader {
+    /// An echo request or reply. `message_type` is an [`IcmpType`] or
+    /// [`Icmpv6Type`] value.
+    ///
+    /// [`IcmpType`]: crate::IcmpType
+    /// [`Icmpv6Type`]: crate::Icmpv6Type
+    pub fn echo(message_type: u8, identifier: u16, sequence_number: u16) -> Self {
+        let mut rest_of_header = [0; 4];
+        rest_of_header[..2].copy_from_slice(&identifier.to_be_bytes());
+        rest_of_header[2..].copy_from_slice(&sequence_number.to_be_bytes());
+        Self {
+            message_type,
+            code: 0,
+            rest_of_header,
+        }
+    }
+}
+
+/// Offsets of the headers in a packet written by a [`PacketBuilder`], for
+/// patching fields in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketLayout {
+    pub network_offset: Option<usize>,
+    pub transport_offset: Option<usize>,
+    pub payload_offset: usize,
+    pub packet_len: usize,
+}
+
+impl PacketBuilder {
+    /// Length of the packet in bytes.
+    pub fn packet_len(&self) -> usize {
+        self.ethernet_len() + self.network_len() + self.transport_len() + self.payload.len()
+    }
+
+    pub fn layout(&self) -> PacketLayout {
+        let network_offset = self.ethernet_len();
+        let transport_offset = network_offset + self.network_len();
+        let payload_offset = transport_offset + self.transport_len();
+        PacketLayout {
+            network_offset: self.network.as_ref().map(|_| network_offset),
+            transport_offset: self.transport.as_ref().map(|_| transport_offset),
+            payload_offset,
+            packet_len: payload_offset + self.payload.len(),
+        }
+    }
+
+    /// Writes the packet to the front of `buffer` and returns its length.
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, PacketError> {
+        if self.ethernet.vlan_tags.len() > EthernetFrame::MAX_VLAN_TAGS {
+            return Err(PacketError::TooManyVlanTags);
+        }
+        if self.network.is_none() && self.transport.is_some() {
+            return Err(PacketError::MissingNetworkHeader);
+        }
+
+        let length = self.packet_len();
+        if buffer.len() < length {
+            return Err(PacketError::BufferTooSmall {
+                needed: length,
+                available: buffer.len(),
+            });
+        }
+
+        let (ethernet, rest) = buffer[..length].split_at_mut(self.ethernet_len());
+        self.write_ethernet(ethernet);
+
+        let (network, rest) = rest.split_at_mut(self.network_len());
+        let (transport, payload) = rest.split_at_mut(self.transport_len());
+        payload.copy_from_slice(&self.payload);
+
+        let upper_layer_len = transport.len() + payload.len();
+        let pseudo_header_sum = match &self.network {
+            Some(NetworkHeader::Ipv4(header)) => {
+                Self::write_ipv4(header, self.protocol(), network, upper_layer_len)?
+            }
+            Some(NetworkHeader::Ipv6(header)) => {
+                Self::write_ipv6(header, self.protocol(), network, upper_layer_len)?
+            }
+            None => return Ok(length),
+        };
+
+        match &self.transport {
+            Some(TransportHeader::Udp(header)) => {
+                write_u16(transport, 0, header.source_port);
+                write_u16(transport, 2, header.destination_port);
+                write_u16(transport, 4, upper_layer_len as u16);
+                write_u16(transport, 6, 0);
+
+                // Zero means that there is no checksum, so it is sent as all
+                // ones instead.
+                let checksum =
+                    match Self::upper_layer_checksum(pseudo_header_sum, transport, payload) {
+                        0 => 0xffff,
+                        checksum => checksum,
+                    };
+                write_u16(transport, 6, checksum);
+            }
+            Some(TransportHeader::Tcp(header)) => {
+                write_u16(transport, 0, header.source_port);
+                write_u16(transport, 2, header.destination_port);
+                write_u32(transport, 4, header.sequence_number);
+                write_u32(transport, 8, header.acknowledgment_number);
+                transport[12] = ((TcpSegment::HEADER_LEN / 4) as u8) << 4;
+                transport[13] = header.flags.0;
+                write_u16(transport, 14, header.window);
+                write_u16(transport, 16, 0);
+                write_u16(transport, 18, header.urgent_pointer);
+
+                let checksum = Self::upper_layer_checksum(pseudo_header_sum, transport, payload);
+                write_u16(transport, 16, checksum);
+            }
+            Some(TransportHeader::Icmp(header)) => {
+                transport[0] = header.message_type;
+                transport[1] = header.code;
+                write_u16(transport, 2, 0);
+                transport[4..8].copy_from_slice(&header.rest_of_header);
+
+                // Unlike ICMPv6, ICMPv4 has no pseudo-header.
+                let pseudo_header_sum = match &self.network {
+                    Some(NetworkHeader::Ipv6(_)) => pseudo_header_sum,
+                    _ => 0,
+                };
+                let checksum = Self::upper_layer_checksum(pseudo_header_sum, transport, payload);
+                write_u16(transport, 2, checksum);
+            }
+            None => {}
+        }
+
+        Ok(length)
+    }
+
+    /// Writes the packet into `frame`, starting at its current headroom, and
+    /// sets the frame length to the packet length. The frame is left as it
+    /// was on error.
+    #[cfg(feature = "libxdp")]
+    pub fn write_frame(&self, frame: &mut Frame<'_>) -> Result<usize, PacketError> {
+        let previous_len = frame.len() as u32;
+        frame.set_len(frame.capacity());
+        match self.write(frame) {
+            Ok(length) => {
+                frame.set_len(length as u32);
+                Ok(length)
+            }
+            Err(error) => {
+                frame.set_len(previous_len);
+                Err(error)
+            }
+        }
+    }
+
+    #[inline]
+    fn ethernet_len(&self) -> usize {
+        EthernetFrame::HEADER_LEN + self.ethernet.vlan_tags.len() * VlanTag::LEN
+    }
+
+    #[inline]
+    fn network_len(&self) -> usize {
+        match self.network {
+            Some(NetworkHeader::Ipv4(_)) => Ipv4Packet::HEADER_LEN,
+            Some(NetworkHeader::Ipv6(_)) => Ipv6Packet::HEADER_LEN,
+            None => 0,
+        }
+    }
+
+    #[inline]
+    fn transport_len(&self) -> usize {
+        match self.transport {
+            Some(TransportHeader::Udp(_)) => UdpDatagram::HEADER_LEN,
+            Some(TransportHeader::Tcp(_)) => TcpSegment::HEADER_LEN,
+            Some(TransportHeader::Icmp(_)) => IcmpPacket::HEADER_LEN,
+            None => 0,
+        }
+    }
+
+    /// Protocol number of the transport header. When the payload follows
+    /// the network header directly, IPv6 says "no next header" and IPv4,
+    /// which has no such number, uses the reserved 255.
+    #[inline]
+    fn protocol(&self) -> IpProtocol {
+        match (&self.transport, &self.network) {
+            (Some(TransportHeader::Udp(_)), _) => IpProtocol::UDP,
+            (Some(TransportHeader::Tcp(_)), _) => IpProtocol::TCP,
+            (Some(TransportHeader::Icmp(_)), Some(NetworkHeader::Ipv6(_))) => IpProtocol::ICMPV6,
+            (Some(TransportHeader::Icmp(_)), _) => IpProtocol::ICMP,
+            (None, Some(NetworkHeader::Ipv6(_))) => IpProtocol::IPV6_NO_NEXT_HEADER,
+            (None, _) => IpProtocol::RESERVED,
+        }
+    }
+
+    fn write_ethernet(&self, buffer: &mut [u8]) {
+        buffer[0..6].copy_from_slice(&self.ethernet.destination);
+        buffer[6..12].copy_from_slice(&self.ethernet.source);
+
+        let mut offset = 12;
+        for tag in &self.ethernet.vlan_tags {
+            write_u16(buffer, offset, tag.tpid.0);
+            write_u16(buffer, offset + 2, tag.tci);
+            offset += VlanTag::LEN;
+        }
+
+        let ether_type = match self.network {
+            Some(NetworkHeader::Ipv4(_)) => EtherType::IPV4,
+            Some(NetworkHeader::Ipv6(_)) => EtherType::IPV6,
+            None => self.ethernet.ether_type,
+        };
+        write_u16(buffer, offset, ether_type.0);
+    }
+
+    /// Returns the pseudo-header sum for the upper-layer checksum.
+    fn write_ipv4(
+        header: &Ipv4Header,
+        protocol: IpProtocol,
+        buffer: &mut [u8],
+        upper_layer_len: usize,
+    ) -> Result<u32, PacketError> {
+        let total_length = buffer.len() + upper_layer_len;
+        if total_length > u16::MAX as usize {
+            return Err(PacketError::InvalidLength {
+                header: "IPv4",
+                length: total_length,
+                available: u16::MAX as usize,
+            });
+        }
+
+        buffer[0] = 0x40 | (Ipv4Packet::HEADER_LEN / 4) as u8;
+        buffer[1] = (header.dscp << 2) | (header.ecn & 0x03);
+        write_u16(buffer, 2, total_length as u16);
+        write_u16(buffer, 4, header.identification);
+        write_u16(buffer, 6, if header.dont_fragment { 0x4000 } else { 0 });
+        buffer[8] = header.ttl;
+        buffer[9] = protocol.0;
+        write_u16(buffer, 10, 0);
+        buffer[12..16].copy_from_slice(&header.source.octets());
+        buffer[16..20].copy_from_slice(&header.destination.octets());
+        write_u16(buffer, 10, checksum::checksum(buffer));
+
+        let accumulator = checksum::sum(0, &buffer[12..20]);
+        Ok(accumulator + protocol.0 as u32 + upper_layer_len as u32)
+    }
+
+    /// Returns the pseudo-header sum for the upper-layer checksum.
+    fn write_ipv6(
+        header: &Ipv6Header,
+        protocol: IpProtocol,
+        buffer: &mut [u8],
+        upper_layer_len: usize,
+    ) -> Result<u32, PacketError> {
+        if upper_layer_len > u16::MAX as usize {
+            return Err(PacketError::InvalidLength {
+                header: "IPv6",
+                length: upper_layer_len,
+                available: u16::MAX as usize,
+            });
+        }
+
+        let first_word =
+            (6 << 28) | ((header.traffic_class as u32) << 20) | (header.flow_label & 0x000f_ffff);
+        write_u32(buffer, 0, first_word);
+        write_u16(buffer, 4, upper_layer_len as u16);
+        buffer[6] = protocol.0;
+        buffer[7] = header.hop_limit;
+        buffer[8..24].copy_from_slice(&header.source.octets());
+        buffer[24..40].copy_from_slice(&header.destination.octets());
+
+        let accumulator = checksum::sum(0, &buffer[8..40]);
+        Ok(accumulator + protocol.0 as u32 + upper_layer_len as u32)
+    }
+
+    #[inline]
+    fn upper_layer_checksum(pseudo_header_sum: u32, header: &[u8], payload: &[u8]) -> u16 {
+        let accumulator = checksum::sum(pseudo_header_sum, header);
+        checksum::fold(checksum::sum(accumulator, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IcmpType, Icmpv6Type};
+
+    fn verify_upper_layer(pseudo_header: &[u8], upper_layer: &[u8]) {
+        let accumulator = checksum::sum(0, pseudo_header);
+        assert_eq!(checksum::fold(checksum::sum(accumulator, upper_layer)), 0);
+    }
+
+    #[test]
+    fn ipv4_udp() {
+        let builder = PacketBuilder {
+            ethernet: EthernetHeader {
+                source: [0x02, 0, 0, 0, 0, 0x01],
+                vlan_tags: vec![VlanTag {
+                    tpid: EtherType::VLAN,
+                    tci: 100,
+                }],
+                ..Default::default()
+            },
+            network: Some(NetworkHeader::Ipv4(Ipv4Header {
+                source: Ipv4Addr::new(10, 0, 0, 1),
+                destination: Ipv4Addr::new(10, 0, 0, 2),
+                ..Default::default()
+            })),
+            transport: Some(TransportHeader::Udp(UdpHeader {
+                source_port: 1234,
+                destination_port: 53,
+            })),
+            payload: b"hello".to_vec(),
+        };
+
+        let mut buffer = [0; 128];
+        let length = builder.write(&mut buffer).unwrap();
+        assert_eq!(length, 18 + 20 + 8 + 5);
+        assert_eq!(length, builder.packet_len());
+        assert_eq!(
+            builder.layout(),
+            PacketLayout {
+                network_offset: Some(18),
+                transport_offset: Some(38),
+                payload_offset: 46,
+                packet_len: 51,
+            }
+        );
+
+        let frame = EthernetFrame::new(&buffer[..length]).unwrap();
+        assert_eq!(frame.vlan_tags().next().unwrap().vlan_id(), 100);
+        assert_eq!(frame.ether_type(), EtherType::IPV4);
+
+        let packet = Ipv4Packet::new(frame.payload()).unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.protocol(), IpProtocol::UDP);
+        assert_eq!(packet.total_length(), 33);
+
+        let datagram = UdpDatagram::new(packet.payload()).unwrap();
+        assert_eq!(datagram.destination_port(), 53);
+        assert_eq!(datagram.payload(), b"hello");
+
+        let mut pseudo_header = packet.header()[12..20].to_vec();
+        pseudo_header.extend_from_slice(&[0, 17, 0, 13]);
+        verify_upper_layer(&pseudo_header, packet.payload());
+    }
+
+    #[test]
+    fn ipv6_tcp() {
+        let builder = PacketBuilder {
+            network: Some(NetworkHeader::Ipv6(Ipv6Header {
+                source: "fd00::1".parse().unwrap(),
+                destination: "fd00::2".parse().unwrap(),
+                flow_label: 0x12345,
+                ..Default::default()
+            })),
+            transport: Some(TransportHeader::Tcp(TcpHeader {
+                source_port: 40000,
+                destination_port: 80,
+                sequence_number: 1,
+                flags: TcpFlags::SYN | TcpFlags::ACK,
+                ..Default::default()
+            })),
+            payload: vec![0xaa; 3],
+            ..Default::default()
+        };
+
+        let mut buffer = [0; 128];
+        let length = builder.write(&mut buffer).unwrap();
+        let frame = EthernetFrame::new(&buffer[..length]).unwrap();
+        assert_eq!(frame.ether_type(), EtherType::IPV6);
+
+        let packet = Ipv6Packet::new(frame.payload()).unwrap();
+        assert_eq!(packet.flow_label(), 0x12345);
+        assert_eq!(packet.payload_length(), 23);
+        assert_eq!(packet.next_header(), IpProtocol::TCP);
+
+        let segment = TcpSegment::new(packet.payload()).unwrap();
+        assert_eq!(segment.header_len(), 20);
+        assert!(segment.flags().contains(TcpFlags::SYN | TcpFlags::ACK));
+        assert_eq!(segment.payload(), &[0xaa; 3]);
+
+        let mut pseudo_header = frame.payload()[8..40].to_vec();
+        pseudo_header.extend_from_slice(&[0, 0, 0, 23, 0, 0, 0, 6]);
+        verify_upper_layer(&pseudo_header, packet.payload());
+    }
+
+    #[test]
+    fn icmp_echo() {
+        let mut builder = PacketBuilder {
+            network: Some(NetworkHeader::Ipv4(Ipv4Header::default())),
+            transport: Some(TransportHeader::Icmp(IcmpHeader::echo(
+                IcmpType::ECHO_REQUEST.0,
+                7,
+                1,
+            ))),
+            payload: vec![1, 2, 3, 4],
+            ..Default::default()
+        };
+
+        let mut buffer = [0; 128];
+        let length = builder.write(&mut buffer).unwrap();
+        let packet = Ipv4Packet::new(&buffer[14..length]).unwrap();
+        assert_eq!(packet.protocol(), IpProtocol::ICMP);
+        let message = IcmpPacket::new(packet.payload()).unwrap();
+        assert_eq!(message.identifier(), 7);
+        assert_eq!(checksum::checksum(packet.payload()), 0);
+
+        builder.network = Some(NetworkHeader::Ipv6(Ipv6Header::default()));
+        builder.transport = Some(TransportHeader::Icmp(IcmpHeader::echo(
+            Icmpv6Type::ECHO_REQUEST.0,
+            7,
+            1,
+        )));
+        let length = builder.write(&mut buffer).unwrap();
+        let packet = Ipv6Packet::new(&buffer[14..length]).unwrap();
+        assert_eq!(packet.next_header(), IpProtocol::ICMPV6);
+        let mut pseudo_header = vec![0; 32];
+        pseudo_header.extend_from_slice(&[0, 0, 0, 12, 0, 0, 0, 58]);
+        verify_upper_layer(&pseudo_header, packet.payload());
+    }
+
+    #[test]
+    fn no_transport() {
+        let mut builder = PacketBuilder {
+            network: Some(NetworkHeader::Ipv4(Ipv4Header::default())),
+            payload: vec![0xaa; 8],
+            ..Default::default()
+        };
+
+        let mut buffer = [0; 128];
+        let length = builder.write(&mut buffer).unwrap();
+        let packet = Ipv4Packet::new(&buffer[14..length]).unwrap();
+        assert_eq!(packet.protocol(), IpProtocol::RESERVED);
+        assert_eq!(packet.payload(), &[0xaa; 8]);
+
+        builder.network = Some(NetworkHeader::Ipv6(Ipv6Header::default()));
+        let length = builder.write(&mut buffer).unwrap();
+        let packet = Ipv6Packet::new(&buffer[14..length]).unwrap();
+        assert_eq!(packet.next_header(), IpProtocol::IPV6_NO_NEXT_HEADER);
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = PacketBuilder {
+            transport: Some(TransportHeader::Udp(UdpHeader::default())),
+            ..Default::default()
+        };
+        assert_eq!(
+            builder.write(&mut [0; 64]),
+            Err(PacketError::MissingNetworkHeader)
+        );
+
+        builder.network = Some(NetworkHeader::Ipv4(Ipv4Header::default()));
+        assert_eq!(
+            builder.write(&mut [0; 40]),
+            Err(PacketError::BufferTooSmall {
+                needed: 42,
+                available: 40
+            })
+        );
+
+        builder.payload = vec![0; u16::MAX as usize];
+        assert!(matches!(
+            builder.write(&mut vec![0; 70_000]),
+            Err(PacketError::InvalidLength { header: "IPv4", .. })
+        ));
+    }
+}
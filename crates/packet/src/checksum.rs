@@ -0,0 +1,77 @@
+//! Internet checksum (RFC 1071).
+
+/// Adds `data` to a running one's complement sum. An odd trailing byte is
+/// padded with zero.
+#[inline]
+pub fn sum(mut accumulator: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        accumulator += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        accumulator += (*last as u32) << 8;
+    }
+    accumulator
+}
+
+/// Folds a running sum into the final 16-bit checksum.
+#[inline]
+pub fn fold(mut accumulator: u32) -> u16 {
+    while accumulator > 0xffff {
+        accumulator = (accumulator & 0xffff) + (accumulator >> 16);
+    }
+    !(accumulator as u16)
+}
+
+/// Computes the checksum of `data`. A buffer that includes its own correct
+/// checksum field yields zero.
+#[inline]
+pub fn checksum(data: &[u8]) -> u16 {
+    fold(sum(0, data))
+}
+
+/// Updates `checksum` after some of the data it covers changed from `old`
+/// to `new` (RFC 1624), without summing the rest of the data again. Both
+/// slices must start at the same even offset into the covered data.
+#[inline]
+pub fn update(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    adjust(checksum, !fold(sum(0, old)), !fold(sum(0, new)))
+}
+
+/// Like [`update`], but takes the folded, uncomplemented sums of the old and
+/// new data, e.g. precomputed sums of a payload prefix.
+#[inline]
+pub fn adjust(checksum: u16, old_sum: u16, new_sum: u16) -> u16 {
+    // HC' = ~(~HC + ~m + m')
+    fold((!checksum) as u32 + (!old_sum) as u32 + new_sum as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc1071_example() {
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(checksum(&data), !0xddf2);
+    }
+
+    #[test]
+    fn odd_length() {
+        assert_eq!(checksum(&[0x01]), !0x0100);
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut data = [0x45, 0x00, 0x00, 0x1c, 0x12, 0x34, 0x0a, 0x00, 0x00, 0x01];
+        let original = checksum(&data);
+
+        data[6..10].copy_from_slice(&[0xc0, 0xa8, 0xff, 0xfe]);
+        let updated = update(original, &[0x0a, 0x00, 0x00, 0x01], &data[6..10]);
+        assert_eq!(updated, checksum(&data));
+
+        // Zeroing the tail is the same as truncating the data.
+        let truncated = adjust(updated, !fold(sum(0, &data)), !fold(sum(0, &data[..5])));
+        assert_eq!(truncated, checksum(&data[..5]));
+    }
+}
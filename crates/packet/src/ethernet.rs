@@ -0,0 +1,171 @@
+use crate::{PacketError, check_length, read_u16};
+
+/// EtherType of an Ethernet II frame or VLAN tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EtherType(pub u16);
+
+impl EtherType {
+    pub const IPV4: Self = Self(0x0800);
+    pub const ARP: Self = Self(0x0806);
+    pub const VLAN: Self = Self(0x8100);
+    pub const QINQ: Self = Self(0x88a8);
+    pub const IPV6: Self = Self(0x86dd);
+
+    /// Returns `true` for the TPIDs of 802.1Q and 802.1ad tags.
+    #[inline]
+    pub fn is_vlan(self) -> bool {
+        self == Self::VLAN || self == Self::QINQ
+    }
+}
+
+/// An 802.1Q or 802.1ad tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VlanTag {
+    pub tpid: EtherType,
+    pub tci: u16,
+}
+
+impl VlanTag {
+    pub const LEN: usize = 4;
+
+    /// Priority code point.
+    #[inline]
+    pub fn pcp(&self) -> u8 {
+        (self.tci >> 13) as u8
+    }
+
+    /// Drop eligible indicator.
+    #[inline]
+    pub fn dei(&self) -> bool {
+        self.tci & 0x1000 != 0
+    }
+
+    #[inline]
+    pub fn vlan_id(&self) -> u16 {
+        self.tci & 0x0fff
+    }
+}
+
+/// An Ethernet II frame with up to two VLAN tags (QinQ).
+#[derive(Clone, Copy, Debug)]
+pub struct EthernetFrame<'a> {
+    buffer: &'a [u8],
+    header_len: usize,
+}
+
+impl<'a> EthernetFrame<'a> {
+    /// Length of the header without VLAN tags.
+    pub const HEADER_LEN: usize = 14;
+    pub const MAX_VLAN_TAGS: usize = 2;
+
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("Ethernet", buffer, Self::HEADER_LEN)?;
+
+        // Skip over the VLAN tags. Each one sits between the source address
+        // and the EtherType of the payload.
+        let mut header_len = Self::HEADER_LEN;
+        while EtherType(read_u16(buffer, header_len - 2)).is_vlan() {
+            if (header_len - Self::HEADER_LEN) / VlanTag::LEN == Self::MAX_VLAN_TAGS {
+                return Err(PacketError::TooManyVlanTags);
+            }
+            header_len += VlanTag::LEN;
+            check_length("VLAN", buffer, header_len)?;
+        }
+
+        Ok(Self { buffer, header_len })
+    }
+
+    #[inline]
+    pub fn destination(&self) -> [u8; 6] {
+        self.buffer[0..6].try_into().unwrap()
+    }
+
+    #[inline]
+    pub fn source(&self) -> [u8; 6] {
+        self.buffer[6..12].try_into().unwrap()
+    }
+
+    /// Tags from the outermost to the innermost.
+    #[inline]
+    pub fn vlan_tags(&self) -> impl Iterator<Item = VlanTag> + 'a {
+        let buffer = self.buffer;
+        (Self::HEADER_LEN - 2..self.header_len - 2)
+            .step_by(VlanTag::LEN)
+            .map(move |offset| VlanTag {
+                tpid: EtherType(read_u16(buffer, offset)),
+                tci: read_u16(buffer, offset + 2),
+            })
+    }
+
+    /// EtherType of the payload, after any VLAN tags.
+    #[inline]
+    pub fn ether_type(&self) -> EtherType {
+        EtherType(read_u16(self.buffer, self.header_len - 2))
+    }
+
+    /// Length of the header including VLAN tags.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        self.header_len
+    }
+
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer[self.header_len..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNTAGGED: [u8; 16] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0xab,
+        0xcd,
+    ];
+
+    #[test]
+    fn untagged() {
+        let frame = EthernetFrame::new(&UNTAGGED).unwrap();
+        assert_eq!(frame.destination(), [0xff; 6]);
+        assert_eq!(frame.source(), [0x02, 0, 0, 0, 0, 0x01]);
+        assert_eq!(frame.ether_type(), EtherType::IPV4);
+        assert_eq!(frame.vlan_tags().count(), 0);
+        assert_eq!(frame.payload(), &[0xab, 0xcd]);
+    }
+
+    #[test]
+    fn qinq() {
+        let mut buffer = UNTAGGED[..12].to_vec();
+        buffer.extend_from_slice(&[0x88, 0xa8, 0x00, 0x64]);
+        buffer.extend_from_slice(&[0x81, 0x00, 0xa0, 0xc8]);
+        buffer.extend_from_slice(&[0x86, 0xdd]);
+
+        let frame = EthernetFrame::new(&buffer).unwrap();
+        let tags: Vec<_> = frame.vlan_tags().collect();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].tpid, EtherType::QINQ);
+        assert_eq!(tags[0].vlan_id(), 100);
+        assert_eq!(tags[1].vlan_id(), 200);
+        assert_eq!(tags[1].pcp(), 5);
+        assert_eq!(frame.ether_type(), EtherType::IPV6);
+        assert_eq!(frame.header_len(), 22);
+        assert!(frame.payload().is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(
+            EthernetFrame::new(&UNTAGGED[..13]).unwrap_err(),
+            PacketError::Truncated {
+                header: "Ethernet",
+                needed: 14,
+                available: 13
+            }
+        );
+
+        let mut buffer = UNTAGGED[..12].to_vec();
+        buffer.extend_from_slice(&[0x81, 0x00, 0x00]);
+        assert!(EthernetFrame::new(&buffer).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{PacketError, check_length, read_u16};
+
+/// ICMPv4 message type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IcmpType(pub u8);
+
+impl IcmpType {
+    pub const ECHO_REPLY: Self = Self(0);
+    pub const DESTINATION_UNREACHABLE: Self = Self(3);
+    pub const REDIRECT: Self = Self(5);
+    pub const ECHO_REQUEST: Self = Self(8);
+    pub const TIME_EXCEEDED: Self = Self(11);
+}
+
+/// ICMPv6 message type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Icmpv6Type(pub u8);
+
+impl Icmpv6Type {
+    pub const DESTINATION_UNREACHABLE: Self = Self(1);
+    pub const PACKET_TOO_BIG: Self = Self(2);
+    pub const TIME_EXCEEDED: Self = Self(3);
+    pub const ECHO_REQUEST: Self = Self(128);
+    pub const ECHO_REPLY: Self = Self(129);
+    pub const ROUTER_SOLICITATION: Self = Self(133);
+    pub const ROUTER_ADVERTISEMENT: Self = Self(134);
+    pub const NEIGHBOR_SOLICITATION: Self = Self(135);
+    pub const NEIGHBOR_ADVERTISEMENT: Self = Self(136);
+}
+
+/// An ICMPv4 (RFC 792) or ICMPv6 (RFC 4443) message. Both share the same
+/// 8-byte header layout; compare [`IcmpPacket::message_type`] against
+/// [`IcmpType`] or [`Icmpv6Type`] accordingly.
+#[derive(Clone, Copy, Debug)]
+pub struct IcmpPacket<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> IcmpPacket<'a> {
+    pub const HEADER_LEN: usize = 8;
+
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("ICMP", buffer, Self::HEADER_LEN)?;
+        Ok(Self { buffer })
+    }
+
+    #[inline]
+    pub fn message_type(&self) -> u8 {
+        self.buffer[0]
+    }
+
+    #[inline]
+    pub fn code(&self) -> u8 {
+        self.buffer[1]
+    }
+
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        read_u16(self.buffer, 2)
+    }
+
+    /// The type-specific second half of the header.
+    #[inline]
+    pub fn rest_of_header(&self) -> [u8; 4] {
+        self.buffer[4..8].try_into().unwrap()
+    }
+
+    /// Identifier of an echo request or reply.
+    #[inline]
+    pub fn identifier(&self) -> u16 {
+        read_u16(self.buffer, 4)
+    }
+
+    /// Sequence number of an echo request or reply.
+    #[inline]
+    pub fn sequence_number(&self) -> u16 {
+        read_u16(self.buffer, 6)
+    }
+
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer[Self::HEADER_LEN..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_request() {
+        let buffer = [0x08, 0x00, 0xf7, 0xfc, 0x00, 0x01, 0x00, 0x02, 0xaa];
+        let packet = IcmpPacket::new(&buffer).unwrap();
+        assert_eq!(IcmpType(packet.message_type()), IcmpType::ECHO_REQUEST);
+        assert_eq!(packet.identifier(), 1);
+        assert_eq!(packet.sequence_number(), 2);
+        assert_eq!(packet.payload(), &[0xaa]);
+
+        assert!(IcmpPacket::new(&buffer[..7]).is_err());
+    }
+}
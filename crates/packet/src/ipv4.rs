@@ -0,0 +1,218 @@
+use crate::{PacketError, check_length, checksum, read_u16};
+use std::net::Ipv4Addr;
+
+/// Protocol number of the payload of an IPv4 packet or the next header of an
+/// IPv6 packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpProtocol(pub u8);
+
+impl IpProtocol {
+    pub const HOP_BY_HOP: Self = Self(0);
+    pub const ICMP: Self = Self(1);
+    pub const TCP: Self = Self(6);
+    pub const UDP: Self = Self(17);
+    pub const IPV6_ROUTING: Self = Self(43);
+    pub const IPV6_FRAGMENT: Self = Self(44);
+    pub const GRE: Self = Self(47);
+    pub const ESP: Self = Self(50);
+    pub const AUTHENTICATION: Self = Self(51);
+    pub const ICMPV6: Self = Self(58);
+    pub const IPV6_NO_NEXT_HEADER: Self = Self(59);
+    pub const IPV6_DESTINATION_OPTIONS: Self = Self(60);
+    /// Reserved, used for IPv4 packets that carry no transport header.
+    pub const RESERVED: Self = Self(255);
+}
+
+/// An IPv4 packet (RFC 791), including options.
+#[derive(Clone, Copy, Debug)]
+pub struct Ipv4Packet<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Ipv4Packet<'a> {
+    /// Length of the header without options.
+    pub const HEADER_LEN: usize = 20;
+
+    /// Checks the version, the header length and the total length. Bytes
+    /// past the total length, such as Ethernet padding, are ignored.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("IPv4", buffer, Self::HEADER_LEN)?;
+        let packet = Self { buffer };
+
+        if packet.version() != 4 {
+            return Err(PacketError::InvalidVersion {
+                header: "IPv4",
+                version: packet.version(),
+            });
+        }
+
+        let header_len = packet.header_len();
+        if header_len < Self::HEADER_LEN {
+            return Err(PacketError::InvalidHeaderLength {
+                header: "IPv4",
+                length: header_len,
+            });
+        }
+        check_length("IPv4", buffer, header_len)?;
+
+        let total_length = packet.total_length() as usize;
+        if total_length < header_len || total_length > buffer.len() {
+            return Err(PacketError::InvalidLength {
+                header: "IPv4",
+                length: total_length,
+                available: buffer.len(),
+            });
+        }
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.buffer[0] >> 4
+    }
+
+    /// Header length in bytes, including options.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        (self.buffer[0] & 0x0f) as usize * 4
+    }
+
+    #[inline]
+    pub fn dscp(&self) -> u8 {
+        self.buffer[1] >> 2
+    }
+
+    #[inline]
+    pub fn ecn(&self) -> u8 {
+        self.buffer[1] & 0x03
+    }
+
+    #[inline]
+    pub fn total_length(&self) -> u16 {
+        read_u16(self.buffer, 2)
+    }
+
+    #[inline]
+    pub fn identification(&self) -> u16 {
+        read_u16(self.buffer, 4)
+    }
+
+    #[inline]
+    pub fn dont_fragment(&self) -> bool {
+        self.buffer[6] & 0x40 != 0
+    }
+
+    #[inline]
+    pub fn more_fragments(&self) -> bool {
+        self.buffer[6] & 0x20 != 0
+    }
+
+    /// Fragment offset in bytes.
+    #[inline]
+    pub fn fragment_offset(&self) -> u16 {
+        (read_u16(self.buffer, 6) & 0x1fff) * 8
+    }
+
+    #[inline]
+    pub fn ttl(&self) -> u8 {
+        self.buffer[8]
+    }
+
+    #[inline]
+    pub fn protocol(&self) -> IpProtocol {
+        IpProtocol(self.buffer[9])
+    }
+
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        read_u16(self.buffer, 10)
+    }
+
+    #[inline]
+    pub fn source(&self) -> Ipv4Addr {
+        <[u8; 4]>::try_from(&self.buffer[12..16]).unwrap().into()
+    }
+
+    #[inline]
+    pub fn destination(&self) -> Ipv4Addr {
+        <[u8; 4]>::try_from(&self.buffer[16..20]).unwrap().into()
+    }
+
+    #[inline]
+    pub fn options(&self) -> &'a [u8] {
+        &self.buffer[Self::HEADER_LEN..self.header_len()]
+    }
+
+    #[inline]
+    pub fn header(&self) -> &'a [u8] {
+        &self.buffer[..self.header_len()]
+    }
+
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer[self.header_len()..self.total_length() as usize]
+    }
+
+    #[inline]
+    pub fn verify_checksum(&self) -> bool {
+        checksum::checksum(self.header()) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A UDP packet with a router alert option, followed by Ethernet padding.
+    fn packet() -> Vec<u8> {
+        let mut buffer = vec![
+            0x46, 0x00, 0x00, 0x1a, 0x1c, 0x46, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 10, 0, 0, 1,
+            10, 0, 0, 2, // fixed header
+            0x94, 0x04, 0x00, 0x00, // router alert option
+            0xaa, 0xbb, // payload
+            0x00, 0x00, // Ethernet padding
+        ];
+        let checksum = checksum::checksum(&buffer[..24]);
+        buffer[10..12].copy_from_slice(&checksum.to_be_bytes());
+        buffer
+    }
+
+    #[test]
+    fn parse_with_options() {
+        let buffer = packet();
+        let packet = Ipv4Packet::new(&buffer).unwrap();
+        assert_eq!(packet.header_len(), 24);
+        assert_eq!(packet.options(), &[0x94, 0x04, 0x00, 0x00]);
+        assert!(packet.dont_fragment());
+        assert_eq!(packet.ttl(), 64);
+        assert_eq!(packet.protocol(), IpProtocol::UDP);
+        assert_eq!(packet.source(), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(packet.destination(), Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(packet.payload(), &[0xaa, 0xbb]);
+        assert!(packet.verify_checksum());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut buffer = packet();
+        buffer[0] = 0x64;
+        assert!(matches!(
+            Ipv4Packet::new(&buffer),
+            Err(PacketError::InvalidVersion { version: 6, .. })
+        ));
+
+        let mut buffer = packet();
+        buffer[0] = 0x44;
+        assert!(matches!(
+            Ipv4Packet::new(&buffer),
+            Err(PacketError::InvalidHeaderLength { length: 16, .. })
+        ));
+
+        let buffer = packet();
+        assert!(matches!(
+            Ipv4Packet::new(&buffer[..25]),
+            Err(PacketError::InvalidLength { length: 26, .. })
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{PacketError, check_length, ipv4::IpProtocol, read_u16, read_u32};
+use std::net::Ipv6Addr;
+
+/// An IPv6 packet (RFC 8200).
+#[derive(Clone, Copy, Debug)]
+pub struct Ipv6Packet<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Ipv6Packet<'a> {
+    pub const HEADER_LEN: usize = 40;
+
+    /// Checks the version and the payload length. Bytes past the payload
+    /// length are ignored.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("IPv6", buffer, Self::HEADER_LEN)?;
+        let packet = Self { buffer };
+
+        if packet.version() != 6 {
+            return Err(PacketError::InvalidVersion {
+                header: "IPv6",
+                version: packet.version(),
+            });
+        }
+
+        let length = Self::HEADER_LEN + packet.payload_length() as usize;
+        if length > buffer.len() {
+            return Err(PacketError::InvalidLength {
+                header: "IPv6",
+                length,
+                available: buffer.len(),
+            });
+        }
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.buffer[0] >> 4
+    }
+
+    #[inline]
+    pub fn traffic_class(&self) -> u8 {
+        (read_u16(self.buffer, 0) >> 4) as u8
+    }
+
+    #[inline]
+    pub fn flow_label(&self) -> u32 {
+        read_u32(self.buffer, 0) & 0x000f_ffff
+    }
+
+    #[inline]
+    pub fn payload_length(&self) -> u16 {
+        read_u16(self.buffer, 4)
+    }
+
+    /// Type of the header right after the fixed header, which may be an
+    /// extension header.
+    #[inline]
+    pub fn next_header(&self) -> IpProtocol {
+        IpProtocol(self.buffer[6])
+    }
+
+    #[inline]
+    pub fn hop_limit(&self) -> u8 {
+        self.buffer[7]
+    }
+
+    #[inline]
+    pub fn source(&self) -> Ipv6Addr {
+        <[u8; 16]>::try_from(&self.buffer[8..24]).unwrap().into()
+    }
+
+    #[inline]
+    pub fn destination(&self) -> Ipv6Addr {
+        <[u8; 16]>::try_from(&self.buffer[24..40]).unwrap().into()
+    }
+
+    /// Everything after the fixed header, including extension headers.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer[Self::HEADER_LEN..Self::HEADER_LEN + self.payload_length() as usize]
+    }
+
+    #[inline]
+    pub fn extension_headers(&self) -> ExtensionHeaders<'a> {
+        ExtensionHeaders {
+            next_header: self.next_header(),
+            buffer: self.payload(),
+            failed: false,
+        }
+    }
+
+    /// Walks the extension headers and returns the upper-layer protocol and
+    /// its bytes.
+    pub fn upper_layer(&self) -> Result<(IpProtocol, &'a [u8]), PacketError> {
+        let mut headers = self.extension_headers();
+        for header in &mut headers {
+            header?;
+        }
+        Ok((headers.next_header, headers.buffer))
+    }
+}
+
+/// An IPv6 extension header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtensionHeader<'a> {
+    /// Type of this header.
+    pub kind: IpProtocol,
+    /// Type of the header that follows.
+    pub next_header: IpProtocol,
+    /// The whole extension header, including the next header byte.
+    pub data: &'a [u8],
+}
+
+impl ExtensionHeader<'_> {
+    /// Returns `true` for the header types this parser knows how to skip.
+    #[inline]
+    pub fn is_extension(protocol: IpProtocol) -> bool {
+        matches!(
+            protocol,
+            IpProtocol::HOP_BY_HOP
+                | IpProtocol::IPV6_ROUTING
+                | IpProtocol::IPV6_FRAGMENT
+                | IpProtocol::AUTHENTICATION
+                | IpProtocol::IPV6_DESTINATION_OPTIONS
+        )
+    }
+}
+
+/// Iterator over the extension headers of an [`Ipv6Packet`]. It stops at the
+/// first header that is not an extension header, or after yielding an
+/// error.
+#[derive(Clone, Debug)]
+pub struct ExtensionHeaders<'a> {
+    next_header: IpProtocol,
+    buffer: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> Iterator for ExtensionHeaders<'a> {
+    type Item = Result<ExtensionHeader<'a>, PacketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || !ExtensionHeader::is_extension(self.next_header) {
+            return None;
+        }
+
+        if let Err(error) = check_length("IPv6 extension", self.buffer, 2) {
+            self.failed = true;
+            return Some(Err(error));
+        }
+
+        // The fragment header has a fixed size, the authentication header
+        // counts 4-byte units and the others count 8-byte units, none of
+        // them including the first 8 bytes.
+        let length = match self.next_header {
+            IpProtocol::IPV6_FRAGMENT => 8,
+            IpProtocol::AUTHENTICATION => (self.buffer[1] as usize + 2) * 4,
+            _ => (self.buffer[1] as usize + 1) * 8,
+        };
+        if let Err(error) = check_length("IPv6 extension", self.buffer, length) {
+            self.failed = true;
+            return Some(Err(error));
+        }
+
+        let (data, rest) = self.buffer.split_at(length);
+        let header = ExtensionHeader {
+            kind: self.next_header,
+            next_header: IpProtocol(data[0]),
+            data,
+        };
+        self.next_header = header.next_header;
+        self.buffer = rest;
+        Some(Ok(header))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buffer = vec![0x60, 0x00, 0x00, 0x00];
+        buffer.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(&[next_header, 64]);
+        buffer.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        buffer.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        buffer.extend_from_slice(payload);
+        buffer
+    }
+
+    #[test]
+    fn walk_extension_headers() {
+        let mut payload = vec![44, 0, 0, 0, 0, 0, 0, 0]; // hop-by-hop
+        payload.extend_from_slice(&[17, 0, 0, 0, 0, 0, 0, 1]); // fragment
+        payload.extend_from_slice(&[0xaa, 0xbb]); // upper layer
+        let buffer = packet(0, &payload);
+
+        let packet = Ipv6Packet::new(&buffer).unwrap();
+        assert_eq!(packet.hop_limit(), 64);
+        assert_eq!(packet.source(), Ipv6Addr::LOCALHOST);
+
+        let kinds: Vec<_> = packet
+            .extension_headers()
+            .map(|header| header.unwrap().kind)
+            .collect();
+        assert_eq!(kinds, [IpProtocol::HOP_BY_HOP, IpProtocol::IPV6_FRAGMENT]);
+
+        let (protocol, upper_layer) = packet.upper_layer().unwrap();
+        assert_eq!(protocol, IpProtocol::UDP);
+        assert_eq!(upper_layer, &[0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn truncated_extension_header() {
+        // The destination options header claims 16 bytes but only 8 are there.
+        let buffer = packet(60, &[17, 1, 0, 0, 0, 0, 0, 0]);
+        let packet = Ipv6Packet::new(&buffer).unwrap();
+        assert!(matches!(
+            packet.upper_layer(),
+            Err(PacketError::Truncated { needed: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_payload_length() {
+        let mut buffer = packet(17, &[0; 8]);
+        buffer.truncate(44);
+        assert!(matches!(
+            Ipv6Packet::new(&buffer),
+            Err(PacketError::InvalidLength { length: 48, .. })
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+//! Zero-copy views over packet headers, and a builder that writes packets
+//! into UMEM frames.
+//!
+//! Every view borrows the packet bytes, checks the bounds once when it is
+//! created and reads fields straight from the buffer afterwards.
+
+pub mod arp;
+pub mod builder;
+pub mod checksum;
+pub mod ethernet;
+pub mod icmp;
+pub mod ipv4;
+pub mod ipv6;
+pub mod tcp;
+pub mod udp;
+
+pub use arp::ArpPacket;
+pub use builder::{
+    EthernetHeader, IcmpHeader, Ipv4Header, Ipv6Header, NetworkHeader, PacketBuilder, PacketLayout,
+    TcpHeader, TransportHeader, UdpHeader,
+};
+pub use ethernet::{EtherType, EthernetFrame, VlanTag};
+pub use icmp::{IcmpPacket, IcmpType, Icmpv6Type};
+pub use ipv4::{IpProtocol, Ipv4Packet};
+pub use ipv6::{ExtensionHeader, ExtensionHeaders, Ipv6Packet};
+pub use tcp::{TcpFlags, TcpSegment};
+pub use udp::UdpDatagram;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PacketError {
+    #[error("Truncated {header} header: needs {needed} bytes but {available} are available")]
+    Truncated {
+        header: &'static str,
+        needed: usize,
+        available: usize,
+    },
+    #[error("Invalid {header} version: {version}")]
+    InvalidVersion { header: &'static str, version: u8 },
+    #[error("Invalid {header} header length: {length}")]
+    InvalidHeaderLength { header: &'static str, length: usize },
+    #[error("Invalid {header} length {length} for a {available} byte buffer")]
+    InvalidLength {
+        header: &'static str,
+        length: usize,
+        available: usize,
+    },
+    #[error("More than two VLAN tags")]
+    TooManyVlanTags,
+    #[error("A transport header needs a network header")]
+    MissingNetworkHeader,
+    #[error("Packet needs {needed} bytes but the buffer has {available}")]
+    BufferTooSmall { needed: usize, available: usize },
+}
+
+#[inline]
+pub(crate) fn check_length(
+    header: &'static str,
+    buffer: &[u8],
+    needed: usize,
+) -> Result<(), PacketError> {
+    if buffer.len() < needed {
+        return Err(PacketError::Truncated {
+            header,
+            needed,
+            available: buffer.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Reads a big-endian `u16` at `offset`.
+#[inline]
+pub fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buffer[offset], buffer[offset + 1]])
+}
+
+/// Reads a big-endian `u32` at `offset`.
+#[inline]
+pub fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        buffer[offset],
+        buffer[offset + 1],
+        buffer[offset + 2],
+        buffer[offset + 3],
+    ])
+}
+
+/// Writes `value` as a big-endian `u16` at `offset`.
+#[inline]
+pub fn write_u16(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Writes `value` as a big-endian `u32` at `offset`.
+#[inline]
+pub fn write_u32(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,141 @@
+use crate::{PacketError, check_length, read_u16, read_u32};
+
+/// TCP control flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TcpFlags(pub u8);
+
+impl TcpFlags {
+    pub const FIN: Self = Self(0x01);
+    pub const SYN: Self = Self(0x02);
+    pub const RST: Self = Self(0x04);
+    pub const PSH: Self = Self(0x08);
+    pub const ACK: Self = Self(0x10);
+    pub const URG: Self = Self(0x20);
+    pub const ECE: Self = Self(0x40);
+    pub const CWR: Self = Self(0x80);
+
+    #[inline]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for TcpFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A TCP segment (RFC 9293), including options.
+#[derive(Clone, Copy, Debug)]
+pub struct TcpSegment<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> TcpSegment<'a> {
+    /// Length of the header without options.
+    pub const HEADER_LEN: usize = 20;
+
+    /// Checks the data offset. `buffer` must end where the IP payload ends.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("TCP", buffer, Self::HEADER_LEN)?;
+        let segment = Self { buffer };
+
+        let header_len = segment.header_len();
+        if header_len < Self::HEADER_LEN {
+            return Err(PacketError::InvalidHeaderLength {
+                header: "TCP",
+                length: header_len,
+            });
+        }
+        check_length("TCP", buffer, header_len)?;
+
+        Ok(segment)
+    }
+
+    #[inline]
+    pub fn source_port(&self) -> u16 {
+        read_u16(self.buffer, 0)
+    }
+
+    #[inline]
+    pub fn destination_port(&self) -> u16 {
+        read_u16(self.buffer, 2)
+    }
+
+    #[inline]
+    pub fn sequence_number(&self) -> u32 {
+        read_u32(self.buffer, 4)
+    }
+
+    #[inline]
+    pub fn acknowledgment_number(&self) -> u32 {
+        read_u32(self.buffer, 8)
+    }
+
+    /// Header length in bytes, including options.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        (self.buffer[12] >> 4) as usize * 4
+    }
+
+    #[inline]
+    pub fn flags(&self) -> TcpFlags {
+        TcpFlags(self.buffer[13])
+    }
+
+    #[inline]
+    pub fn window(&self) -> u16 {
+        read_u16(self.buffer, 14)
+    }
+
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        read_u16(self.buffer, 16)
+    }
+
+    #[inline]
+    pub fn urgent_pointer(&self) -> u16 {
+        read_u16(self.buffer, 18)
+    }
+
+    #[inline]
+    pub fn options(&self) -> &'a [u8] {
+        &self.buffer[Self::HEADER_LEN..self.header_len()]
+    }
+
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer[self.header_len()..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syn_with_options() {
+        let buffer = [
+            0x30, 0x39, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x60, 0x02,
+            0xff, 0xff, 0x12, 0x34, 0x00, 0x00, // fixed header
+            0x02, 0x04, 0x05, 0xb4, // MSS option
+        ];
+        let segment = TcpSegment::new(&buffer).unwrap();
+        assert_eq!(segment.source_port(), 12345);
+        assert_eq!(segment.destination_port(), 80);
+        assert_eq!(segment.sequence_number(), 1);
+        assert!(segment.flags().contains(TcpFlags::SYN));
+        assert!(!segment.flags().contains(TcpFlags::SYN | TcpFlags::ACK));
+        assert_eq!(segment.options(), &[0x02, 0x04, 0x05, 0xb4]);
+        assert!(segment.payload().is_empty());
+
+        assert!(matches!(
+            TcpSegment::new(&buffer[..22]),
+            Err(PacketError::Truncated { needed: 24, .. })
+        ));
+    }
+}
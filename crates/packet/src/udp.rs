@@ -0,0 +1,75 @@
+use crate::{PacketError, check_length, read_u16};
+
+/// A UDP datagram (RFC 768).
+#[derive(Clone, Copy, Debug)]
+pub struct UdpDatagram<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> UdpDatagram<'a> {
+    pub const HEADER_LEN: usize = 8;
+
+    /// Checks the length field against `buffer`.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, PacketError> {
+        check_length("UDP", buffer, Self::HEADER_LEN)?;
+        let datagram = Self { buffer };
+
+        let length = datagram.length() as usize;
+        if length < Self::HEADER_LEN || length > buffer.len() {
+            return Err(PacketError::InvalidLength {
+                header: "UDP",
+                length,
+                available: buffer.len(),
+            });
+        }
+
+        Ok(datagram)
+    }
+
+    #[inline]
+    pub fn source_port(&self) -> u16 {
+        read_u16(self.buffer, 0)
+    }
+
+    #[inline]
+    pub fn destination_port(&self) -> u16 {
+        read_u16(self.buffer, 2)
+    }
+
+    /// Length of the header and payload in bytes.
+    #[inline]
+    pub fn length(&self) -> u16 {
+        read_u16(self.buffer, 4)
+    }
+
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        read_u16(self.buffer, 6)
+    }
+
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer[Self::HEADER_LEN..self.length() as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let buffer = [
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0a, 0x00, 0x00, 0xaa, 0xbb, 0x00,
+        ];
+        let datagram = UdpDatagram::new(&buffer).unwrap();
+        assert_eq!(datagram.source_port(), 1234);
+        assert_eq!(datagram.destination_port(), 53);
+        assert_eq!(datagram.payload(), &[0xaa, 0xbb]);
+
+        assert!(matches!(
+            UdpDatagram::new(&buffer[..9]),
+            Err(PacketError::InvalidLength { length: 10, .. })
+        ));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::{ThreadError, get_core_id, signal, spawn_on};
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// A flag that asks workers to stop. Clones share the flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TokenInner {
+    cancelled: AtomicBool,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token and wakes up every [`CancellationToken::wait_timeout`].
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Sleeps until the token is cancelled or `timeout` passes, and returns
+    /// whether it is cancelled. Use it instead of [`std::thread::sleep`] so
+    /// that a stop request is not delayed by the sleep.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let guard = lock(&self.inner.lock);
+        let _guard = self
+            .inner
+            .condvar
+            .wait_timeout_while(guard, timeout, |_| !self.is_cancelled())
+            .unwrap_or_else(PoisonError::into_inner);
+        self.is_cancelled()
+    }
+
+    #[inline]
+    pub(crate) fn inner(&self) -> &Arc<TokenInner> {
+        &self.inner
+    }
+}
+
+impl TokenInner {
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        // Take the lock so that a waiter cannot miss the notification
+        // between checking the flag and going to sleep.
+        let _guard = lock(&self.lock);
+        self.condvar.notify_all();
+    }
+}
+
+/// Pinned worker threads that share a [`CancellationToken`].
+///
+/// A worker that returns an error or panics cancels the token, so the
+/// others stop too, and [`WorkerGroup::join`] returns the first failure.
+/// Dropping the group cancels the token and waits for every worker.
+#[derive(Debug)]
+pub struct WorkerGroup<E> {
+    token: CancellationToken,
+    handles: Vec<JoinHandle<()>>,
+    shared: Arc<Shared<E>>,
+}
+
+#[derive(Debug)]
+struct Shared<E> {
+    state: Mutex<State<E>>,
+    finished: Condvar,
+}
+
+#[derive(Debug)]
+struct State<E> {
+    running: usize,
+    failure: Option<GroupError<E>>,
+}
+
+impl<E: Send + 'static> Default for WorkerGroup<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Send + 'static> WorkerGroup<E> {
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            handles: Vec::new(),
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    running: 0,
+                    failure: None,
+                }),
+                finished: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Spawns a worker pinned to `core_id`. The worker gets the token of
+    /// the group and should return once it is cancelled.
+    pub fn spawn<F>(&mut self, core_id: usize, function: F) -> Result<(), ThreadError>
+    where
+        F: FnOnce(CancellationToken) -> Result<(), E> + Send + 'static,
+    {
+        let core = get_core_id(core_id)?;
+        lock(&self.shared.state).running += 1;
+        let completion = Completion {
+            shared: self.shared.clone(),
+            token: self.token.clone(),
+            core_id,
+            failure: None,
+            finished: false,
+        };
+        let token = self.token.clone();
+        let handle = spawn_on(core, move || {
+            let mut completion = completion;
+            completion.failure = match panic::catch_unwind(AssertUnwindSafe(|| function(token))) {
+                Ok(Ok(())) => None,
+                Ok(Err(error)) => Some(GroupError::Worker { core_id, error }),
+                Err(payload) => Some(GroupError::Panicked {
+                    core_id,
+                    message: panic_message(payload.as_ref()),
+                }),
+            };
+            completion.finished = true;
+        });
+        self.handles.push(handle);
+        Ok(())
+    }
+
+    /// A clone of the token that every worker gets.
+    #[inline]
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Asks every worker to stop. Use [`WorkerGroup::join`] to wait for
+    /// them.
+    #[inline]
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Cancels the group on SIGINT or SIGTERM. A second signal terminates
+    /// the process as usual, in case the workers do not stop.
+    pub fn cancel_on_signal(&self) -> Result<(), ThreadError> {
+        signal::register(&self.token).map_err(ThreadError::SignalHandler)
+    }
+
+    /// Number of workers that have not returned yet.
+    #[inline]
+    pub fn running(&self) -> usize {
+        lock(&self.shared.state).running
+    }
+
+    /// Returns `true` when every worker has returned.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.running() == 0
+    }
+
+    /// Waits for every worker and returns the first failure, if any.
+    pub fn join(mut self) -> Result<(), GroupError<E>> {
+        for handle in self.handles.drain(..) {
+            // Failures, including panics, are recorded by the worker.
+            let _ = handle.join();
+        }
+        self.take_failure()
+    }
+
+    /// Like [`WorkerGroup::join`], but gives up after `timeout`. The workers
+    /// that are still running then are cancelled and detached.
+    pub fn join_timeout(mut self, timeout: Duration) -> Result<(), GroupError<E>> {
+        let state = lock(&self.shared.state);
+        let state = self
+            .shared
+            .finished
+            .wait_timeout_while(state, timeout, |state| state.running > 0)
+            .map_or_else(|error| error.into_inner().0, |(state, _)| state);
+        let running = state.running;
+        drop(state);
+
+        if running > 0 {
+            self.token.cancel();
+            self.handles.clear();
+            return Err(GroupError::Timeout { running, timeout });
+        }
+        self.join()
+    }
+
+    fn take_failure(&self) -> Result<(), GroupError<E>> {
+        match lock(&self.shared.state).failure.take() {
+            Some(failure) => Err(failure),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> Drop for WorkerGroup<E> {
+    fn drop(&mut self) {
+        if self.handles.is_empty() {
+            return;
+        }
+        self.token.cancel();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Records how a worker ended when it is dropped, which also happens when
+/// the thread panics before the worker runs.
+struct Completion<E> {
+    shared: Arc<Shared<E>>,
+    token: CancellationToken,
+    core_id: usize,
+    failure: Option<GroupError<E>>,
+    finished: bool,
+}
+
+impl<E> Drop for Completion<E> {
+    fn drop(&mut self) {
+        let failure = match self.finished {
+            true => self.failure.take(),
+            false => Some(GroupError::Panicked {
+                core_id: self.core_id,
+                message: "The worker thread exited before the worker ran".to_owned(),
+            }),
+        };
+
+        let mut state = lock(&self.shared.state);
+        if let Some(failure) = failure {
+            self.token.cancel();
+            state.failure.get_or_insert(failure);
+        }
+        state.running -= 1;
+        self.shared.finished.notify_all();
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Unknown panic payload".to_owned(),
+        },
+    }
+}
+
+/// Locks a mutex that is never left in an inconsistent state, so poisoning
+/// can be ignored.
+#[inline]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GroupError<E> {
+    #[error("Worker on core {core_id} failed: {error}")]
+    Worker { core_id: usize, error: E },
+    #[error("Worker on core {core_id} panicked: {message}")]
+    Panicked { core_id: usize, message: String },
+    #[error("{running} workers did not stop within {timeout:?}")]
+    Timeout { running: usize, timeout: Duration },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn cancel_and_join() {
+        let mut group = WorkerGroup::<()>::new();
+        for _ in 0..4 {
+            group
+                .spawn(0, |token| {
+                    while !token.wait_timeout(Duration::from_secs(10)) {}
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert_eq!(group.running(), 4);
+
+        let started_at = Instant::now();
+        group.cancel();
+        group.join().unwrap();
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn panic_is_reported() {
+        let mut group = WorkerGroup::<()>::new();
+        let token = group.token();
+        group.spawn(0, |_| panic!("boom")).unwrap();
+
+        let error = group.join().unwrap_err();
+        assert!(matches!(
+            &error,
+            GroupError::Panicked { core_id: 0, message } if message == "boom"
+        ));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn join_timeout() {
+        let mut group = WorkerGroup::<()>::new();
+        group
+            .spawn(0, |_| {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            })
+            .unwrap();
+        assert!(matches!(
+            group.join_timeout(Duration::from_millis(10)),
+            Err(GroupError::Timeout { running: 1, .. })
+        ));
+
+        let mut group = WorkerGroup::<()>::new();
+        group.spawn(0, |_| Ok(())).unwrap();
+        group.join_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(
+            WorkerGroup::<()>::new().spawn(usize::MAX, |_| Ok(())),
+            Err(ThreadError::InvalidCoreId(usize::MAX))
+        ));
+    }
+
+    #[test]
+    fn cancel_on_signal() {
+        let mut group = WorkerGroup::<()>::new();
+        group.cancel_on_signal().unwrap();
+        group
+            .spawn(0, |token| {
+                while !token.wait_timeout(Duration::from_secs(10)) {}
+                Ok(())
+            })
+            .unwrap();
+
+        unsafe { libc::raise(libc::SIGTERM) };
+        group.join_timeout(Duration::from_secs(5)).unwrap();
+    }
+}
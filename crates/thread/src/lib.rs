@@ -1,3 +1,10 @@
+mod group;
+mod signal;
+mod topology;
+
+pub use group::{CancellationToken, GroupError, WorkerGroup};
+pub use topology::{NumaNode, Topology, interface_numa_node, prefer_memory_on_node};
+
 use core_affinity::CoreId;
 use std::thread::{self, JoinHandle};
 
@@ -8,6 +15,13 @@ pub fn available_cores() -> Result<usize, ThreadError> {
         .ok_or(ThreadError::UnableToGetCoreIds)
 }
 
+/// Get the IDs of the cores this process is allowed to run on.
+pub fn core_ids() -> Result<Vec<usize>, ThreadError> {
+    core_affinity::get_core_ids()
+        .map(|core_ids| core_ids.into_iter().map(|core| core.id).collect())
+        .ok_or(ThreadError::UnableToGetCoreIds)
+}
+
 fn get_core_id(core_id: usize) -> Result<CoreId, ThreadError> {
     core_affinity::get_core_ids()
         .ok_or(ThreadError::UnableToGetCoreIds)?
@@ -23,15 +37,21 @@ where
     T: Send + 'static,
 {
     let core_id = get_core_id(core_id)?;
-    let handle = thread::spawn(move || {
+    Ok(spawn_on(core_id, function))
+}
+
+fn spawn_on<F, T>(core_id: CoreId, function: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    thread::spawn(move || {
         if core_affinity::set_for_current(core_id) {
             function()
         } else {
             panic!("Unable to pin the thread to {core_id:?}. This is a bug.");
         }
-    });
-
-    Ok(handle)
+    })
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -40,15 +60,25 @@ pub enum ThreadError {
     UnableToGetCoreIds,
     #[error("Invalid core ID: {0}")]
     InvalidCoreId(usize),
+    #[error("Failed to read the NUMA topology: {0}")]
+    Topology(std::io::Error),
+    #[error("Invalid CPU list: {0}")]
+    InvalidCpuList(String),
+    #[error("Invalid NUMA node: {0}")]
+    InvalidNumaNode(u32),
+    #[error("Failed to set the memory policy: {0}")]
+    MemoryPolicy(std::io::Error),
+    #[error("Failed to install the signal handler: {0}")]
+    SignalHandler(std::io::Error),
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{ThreadError, spawn};
+    use crate::{GroupError, ThreadError, WorkerGroup, spawn};
     use std::{
         thread,
-        thread::{JoinHandle, sleep},
-        time::Duration,
+        thread::JoinHandle,
+        time::{Duration, Instant},
     };
 
     #[test]
@@ -71,28 +101,29 @@ mod test {
 
     #[test]
     fn test_join_multiple_workers() {
-        let mut handles = Vec::<Option<JoinHandle<Result<(), ThreadError>>>>::new();
-
-        let handle_1 = spawn(0, move || {
-            sleep(Duration::from_millis(3000));
-            Ok(())
-        })
-        .unwrap();
-        handles.push(Some(handle_1));
-
-        let handle_2 = spawn(1, move || Err(ThreadError::UnableToGetCoreIds)).unwrap();
-        handles.push(Some(handle_2));
-
-        loop {
-            for worker in handles.iter_mut() {
-                if let Some(handle) = worker.take() {
-                    if handle.is_finished() {
-                        handle.join().unwrap().unwrap();
-                    } else {
-                        worker.replace(handle);
-                    }
+        let mut group = WorkerGroup::new();
+        group
+            .spawn(0, move |token| {
+                // Returns early once the other worker fails.
+                match token.wait_timeout(Duration::from_millis(3000)) {
+                    true => Ok(()),
+                    false => Err(ThreadError::InvalidCoreId(0)),
                 }
+            })
+            .unwrap();
+        group
+            .spawn(0, move |_| Err(ThreadError::UnableToGetCoreIds))
+            .unwrap();
+
+        let started_at = Instant::now();
+        let error = group.join().unwrap_err();
+        assert!(matches!(
+            error,
+            GroupError::Worker {
+                core_id: 0,
+                error: ThreadError::UnableToGetCoreIds
             }
-        }
+        ));
+        assert!(started_at.elapsed() < Duration::from_millis(3000));
     }
 }
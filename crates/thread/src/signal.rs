@@ -0,0 +1,98 @@
+use crate::{CancellationToken, group::TokenInner};
+use libc::{SIG_DFL, SIGINT, SIGTERM, c_int, c_void};
+use std::{
+    io,
+    ptr::null_mut,
+    sync::{
+        Arc, Mutex, OnceLock, PoisonError, Weak,
+        atomic::{AtomicBool, AtomicI32, Ordering},
+    },
+};
+
+/// Write end of the pipe that the signal handler wakes the watcher with.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+/// Set once a signal arrived, so that late registrations cancel at once.
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+static TOKENS: Mutex<Vec<Weak<TokenInner>>> = Mutex::new(Vec::new());
+/// The result of installing the handler, as an OS error code.
+static INSTALLED: OnceLock<Result<(), i32>> = OnceLock::new();
+
+/// Cancels `token` on the next SIGINT or SIGTERM.
+///
+/// Cancelling takes locks, which a signal handler must not do. The handler
+/// only writes to a pipe, and a watcher thread does the cancelling.
+pub(crate) fn register(token: &CancellationToken) -> io::Result<()> {
+    INSTALLED
+        .get_or_init(|| install().map_err(|error| error.raw_os_error().unwrap_or(0)))
+        .map_err(io::Error::from_raw_os_error)?;
+
+    let mut tokens = TOKENS.lock().unwrap_or_else(PoisonError::into_inner);
+    if SIGNALLED.load(Ordering::Acquire) {
+        token.cancel();
+        return Ok(());
+    }
+    tokens.retain(|token| token.strong_count() > 0);
+    tokens.push(Arc::downgrade(token.inner()));
+    Ok(())
+}
+
+fn install() -> io::Result<()> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+    PIPE.store(write_fd, Ordering::Release);
+    std::thread::Builder::new()
+        .name("mangonel-signal".to_owned())
+        .spawn(move || watch(read_fd))?;
+
+    for signal in [SIGINT, SIGTERM] {
+        // SAFETY: An all-zero sigaction is a valid value to fill in.
+        let mut action = unsafe { std::mem::zeroed::<libc::sigaction>() };
+        action.sa_sigaction = handle_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        unsafe { libc::sigemptyset(&mut action.sa_mask) };
+        if unsafe { libc::sigaction(signal, &action, null_mut()) }.is_negative() {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+extern "C" fn handle_signal(_: c_int) {
+    // SAFETY: `write` is async-signal-safe. The errno it may set is restored
+    // so that the interrupted code does not see it.
+    unsafe {
+        let errno = *libc::__errno_location();
+        let byte = 1u8;
+        libc::write(PIPE.load(Ordering::Acquire), (&raw const byte).cast(), 1);
+        *libc::__errno_location() = errno;
+    }
+}
+
+fn watch(read_fd: c_int) {
+    let mut byte = 0u8;
+    loop {
+        let length = unsafe { libc::read(read_fd, (&raw mut byte).cast::<c_void>(), 1) };
+        match length {
+            1 => break,
+            length
+                if length.is_negative()
+                    && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+            _ => return,
+        }
+    }
+
+    // Let a second signal terminate the process when the workers hang.
+    for signal in [SIGINT, SIGTERM] {
+        unsafe { libc::signal(signal, SIG_DFL) };
+    }
+
+    let mut tokens = TOKENS.lock().unwrap_or_else(PoisonError::into_inner);
+    SIGNALLED.store(true, Ordering::Release);
+    for token in tokens.drain(..).filter_map(|token| token.upgrade()) {
+        token.cancel();
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{ThreadError, core_ids};
+use mangonel_util::numa;
+use std::{fs, io, path::Path};
+
+/// A NUMA node and the cores that belong to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NumaNode {
+    id: u32,
+    cores: Vec<usize>,
+}
+
+impl NumaNode {
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    #[inline]
+    pub fn cores(&self) -> &[usize] {
+        &self.cores
+    }
+}
+
+/// The NUMA nodes of the system, read from `/sys/devices/system/node`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    nodes: Vec<NumaNode>,
+}
+
+impl Topology {
+    const NODE_PATH: &str = "/sys/devices/system/node";
+
+    /// Reads the topology from sysfs. A kernel without NUMA support has no
+    /// node directory and is reported as a single node with every core.
+    pub fn discover() -> Result<Self, ThreadError> {
+        match Self::from_sysfs(Path::new(Self::NODE_PATH)) {
+            Err(ThreadError::Topology(error)) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(Self {
+                    nodes: vec![NumaNode {
+                        id: 0,
+                        cores: core_ids()?,
+                    }],
+                })
+            }
+            result => result,
+        }
+    }
+
+    fn from_sysfs(root: &Path) -> Result<Self, ThreadError> {
+        let mut nodes = Vec::new();
+        for entry in fs::read_dir(root).map_err(ThreadError::Topology)? {
+            let entry = entry.map_err(ThreadError::Topology)?;
+            let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|id| id.parse().ok())
+            else {
+                continue;
+            };
+
+            let cpulist =
+                fs::read_to_string(entry.path().join("cpulist")).map_err(ThreadError::Topology)?;
+            let cores = parse_cpu_list(&cpulist)
+                .ok_or_else(|| ThreadError::InvalidCpuList(cpulist.trim().to_owned()))?;
+            nodes.push(NumaNode { id, cores });
+        }
+        nodes.sort_by_key(|node| node.id);
+
+        Ok(Self { nodes })
+    }
+
+    #[inline]
+    pub fn nodes(&self) -> &[NumaNode] {
+        &self.nodes
+    }
+
+    #[inline]
+    pub fn node(&self, id: u32) -> Option<&NumaNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// The node that `core_id` belongs to.
+    pub fn node_of_core(&self, core_id: usize) -> Option<u32> {
+        self.nodes
+            .iter()
+            .find(|node| node.cores.contains(&core_id))
+            .map(|node| node.id)
+    }
+
+    /// Cores on `node` that this process is allowed to run on, e.g. to pin
+    /// workers next to a NIC.
+    pub fn available_cores(&self, node: u32) -> Result<Vec<usize>, ThreadError> {
+        let node = self.node(node).ok_or(ThreadError::InvalidNumaNode(node))?;
+        let available = core_ids()?;
+        Ok(node
+            .cores
+            .iter()
+            .copied()
+            .filter(|core| available.contains(core))
+            .collect())
+    }
+}
+
+/// NUMA node of the device behind a network interface. Virtual devices have
+/// none, and single-node systems report none.
+pub fn interface_numa_node(interface_name: &str) -> Option<u32> {
+    let path = Path::new("/sys/class/net")
+        .join(interface_name)
+        .join("device/numa_node");
+    // `-1` means that the node is unknown, and fails to parse.
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Makes the kernel prefer `node` for the memory the calling thread
+/// allocates from now on, with `set_mempolicy`.
+pub fn prefer_memory_on_node(node: u32) -> Result<(), ThreadError> {
+    numa::prefer_node(node).map_err(ThreadError::MemoryPolicy)
+}
+
+/// Parses a kernel CPU list such as `0-3,8,10-11`.
+fn parse_cpu_list(cpulist: &str) -> Option<Vec<usize>> {
+    let mut cores = Vec::new();
+    for range in cpulist.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cores.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => cores.push(range.parse().ok()?),
+        }
+    }
+    Some(cores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_list() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_cpu_list("5"), Some(vec![5]));
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        assert_eq!(parse_cpu_list("0-a"), None);
+    }
+
+    #[test]
+    fn sysfs_nodes() {
+        let root = std::env::temp_dir().join(format!("mangonel-topology-{}", std::process::id()));
+        for (node, cpulist) in [("node1", "4-7\n"), ("node0", "0-3\n")] {
+            fs::create_dir_all(root.join(node)).unwrap();
+            fs::write(root.join(node).join("cpulist"), cpulist).unwrap();
+        }
+        fs::create_dir_all(root.join("power")).unwrap();
+
+        let topology = Topology::from_sysfs(&root);
+        fs::remove_dir_all(&root).unwrap();
+        let topology = topology.unwrap();
+        assert_eq!(topology.nodes().len(), 2);
+        assert_eq!(topology.nodes()[0].id(), 0);
+        assert_eq!(topology.node(1).unwrap().cores(), [4, 5, 6, 7]);
+        assert_eq!(topology.node_of_core(5), Some(1));
+        assert_eq!(topology.node_of_core(8), None);
+        assert!(matches!(
+            topology.available_cores(2),
+            Err(ThreadError::InvalidNumaNode(2))
+        ));
+    }
+
+    #[test]
+    fn discover() {
+        let topology = Topology::discover().unwrap();
+        assert!(!topology.nodes().is_empty());
+        let node = topology.node_of_core(core_ids().unwrap()[0]).unwrap();
+        assert!(!topology.available_cores(node).unwrap().is_empty());
+        assert_eq!(interface_numa_node("mangonel-none"), None);
+    }
+}
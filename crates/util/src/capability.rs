@@ -0,0 +1,805 @@
+//! Checks at runtime what the kernel and a driver support for AF_XDP.
+//!
+//! The checks run where the sockets are created. The build host is the
+//! wrong machine to ask when cross compiling.
+
+use crate::{
+    netlink::{AttributeWriter, Attributes, GenericNetlink, NetlinkError, RouteNetlink},
+    system::{SystemInfoError, kernel_version},
+};
+use libc::{
+    AF_XDP, SOCK_CLOEXEC, SOCK_RAW, SOL_XDP, XDP_MMAP_OFFSETS, XDP_OPTIONS, XDP_OPTIONS_ZEROCOPY,
+    XDP_RX_RING, XDP_UMEM_COMPLETION_RING, XDP_UMEM_FILL_RING, XDP_UMEM_REG, XDP_ZEROCOPY, c_int,
+    c_void, socklen_t,
+};
+use std::{
+    ffi::{CStr, CString},
+    fs, io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+    ptr::null_mut,
+};
+
+const HUGEPAGES_PATH: &str = "/sys/kernel/mm/hugepages";
+const NODE_PATH: &str = "/sys/devices/system/node";
+
+const NETDEV_FAMILY_NAME: &str = "netdev";
+const NETDEV_FAMILY_VERSION: u8 = 1;
+const NETDEV_CMD_DEV_GET: u8 = 1;
+const NETDEV_A_DEV_IFINDEX: u16 = 1;
+const NETDEV_A_DEV_XDP_FEATURES: u16 = 3;
+const NETDEV_A_DEV_XDP_ZC_MAX_SEGS: u16 = 4;
+const NETDEV_XDP_ACT_BASIC: u64 = 1 << 0;
+const NETDEV_XDP_ACT_XSK_ZEROCOPY: u64 = 1 << 3;
+const NETDEV_XDP_ACT_RX_SG: u64 = 1 << 5;
+
+const IFLA_XDP: u16 = 43;
+const IFLA_XDP_FD: u16 = 1;
+const IFLA_XDP_ATTACHED: u16 = 2;
+const IFLA_XDP_FLAGS: u16 = 3;
+const IFLA_XDP_DRV_PROG_ID: u16 = 5;
+const XDP_ATTACHED_DRV: u8 = 1;
+const XDP_ATTACHED_SKB: u8 = 2;
+const XDP_ATTACHED_HW: u8 = 3;
+const XDP_ATTACHED_MULTI: u8 = 4;
+const XDP_FLAGS_UPDATE_IF_NOEXIST: u32 = 1 << 0;
+const XDP_FLAGS_DRV_MODE: u32 = 1 << 2;
+
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_PROG_TYPE_XDP: u32 = 6;
+const XDP_PASS: i32 = 2;
+
+/// Whether a capability is available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Support {
+    Supported,
+    Unsupported,
+    /// The check was not possible, e.g. without `CAP_NET_RAW` or when the
+    /// queue is in use.
+    Unknown,
+}
+
+impl Support {
+    #[inline]
+    pub fn is_supported(self) -> bool {
+        self == Self::Supported
+    }
+
+    #[inline]
+    pub fn is_unsupported(self) -> bool {
+        self == Self::Unsupported
+    }
+
+    #[inline]
+    fn from_bool(supported: bool) -> Self {
+        match supported {
+            true => Self::Supported,
+            false => Self::Unsupported,
+        }
+    }
+
+    /// Maps the outcome of a syscall that fails when the capability is
+    /// missing.
+    fn from_result<T>(result: &io::Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Supported,
+            Err(error) => match error.raw_os_error() {
+                Some(libc::EAFNOSUPPORT | libc::EOPNOTSUPP | libc::EINVAL) => Self::Unsupported,
+                _ => Self::Unknown,
+            },
+        }
+    }
+}
+
+/// How [`Capabilities::probe_interface`] checks a driver that does not
+/// report its XDP features, i.e. on kernels older than Linux 6.3.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProbeMode {
+    /// Only reads the state of the interface. Zero-copy stays
+    /// [`Support::Unknown`], and so does native XDP unless a program already
+    /// runs in the driver.
+    #[default]
+    ReadOnly,
+    /// Binds a socket in zero-copy mode to the queue and attaches a program
+    /// that passes every packet in driver mode, then removes both.
+    ///
+    /// Many drivers reset their queues to do so and drop traffic meanwhile.
+    /// A process that dies before the program is detached leaves it
+    /// attached, and it has to be removed with `ip link set dev <name> xdp
+    /// off`.
+    Attach,
+}
+
+/// Hugepages of one size, from `/sys/kernel/mm/hugepages`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HugepagePool {
+    /// Page size in bytes.
+    pub size: usize,
+    pub total: u64,
+    pub free: u64,
+    /// Free pages that mappings have already reserved and will fault in
+    /// later.
+    pub reserved: u64,
+}
+
+impl HugepagePool {
+    /// Reads the pool of `size` byte pages, or `None` when the kernel has no
+    /// such pool.
+    ///
+    /// With `numa_node`, `total` and `free` count the pages of that node.
+    /// The kernel does not track reservations per node, so the reservations
+    /// of the whole system count against it.
+    pub fn read(size: usize, numa_node: Option<u32>) -> Result<Option<Self>, ProbeError> {
+        let name = format!("hugepages-{}kB", size / 1024);
+        let path = Path::new(HUGEPAGES_PATH).join(&name);
+        if !path.is_dir() {
+            return Ok(None);
+        }
+        let pool = read_pool(&path, size)?;
+        let Some(numa_node) = numa_node else {
+            return Ok(Some(pool));
+        };
+
+        // Kernels without NUMA support have no node directories.
+        let path = Path::new(NODE_PATH)
+            .join(format!("node{numa_node}"))
+            .join("hugepages")
+            .join(name);
+        if !path.is_dir() {
+            return Ok(Some(pool));
+        }
+        let node_pool = read_pool(&path, size)?;
+        Ok(Some(Self {
+            reserved: pool.reserved,
+            ..node_pool
+        }))
+    }
+
+    /// Number of pages that a new mapping can still get.
+    #[inline]
+    pub fn available(&self) -> u64 {
+        self.free.saturating_sub(self.reserved)
+    }
+}
+
+/// What the running kernel supports for AF_XDP.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Major and minor version.
+    pub kernel_version: (u32, u32),
+    /// Whether AF_XDP sockets can be created.
+    pub af_xdp: Support,
+    /// `XDP_USE_NEED_WAKEUP`, Linux 5.4 and newer.
+    pub need_wakeup: Support,
+    /// Sharing a UMEM between queues and interfaces, Linux 5.10 and newer.
+    pub shared_umem: Support,
+    /// `XDP_USE_SG` for frames that span multiple buffers, Linux 6.6 and
+    /// newer.
+    pub multi_buffer: Support,
+    pub hugepages: Vec<HugepagePool>,
+    /// What the driver supports, with [`Capabilities::probe_interface`].
+    pub interface: Option<InterfaceCapabilities>,
+}
+
+impl Capabilities {
+    /// Checks what the kernel supports, without touching any interface.
+    pub fn probe() -> Result<Self, ProbeError> {
+        let kernel_version = kernel_version()?;
+        let socket = xdp_socket();
+        let af_xdp = Support::from_result(&socket);
+        let at_least = |version: (u32, u32)| match af_xdp {
+            Support::Unsupported => Support::Unsupported,
+            _ => Support::from_bool(kernel_version >= version),
+        };
+
+        // The ring offsets grew a flags field together with need_wakeup.
+        let need_wakeup = match &socket {
+            Ok(socket) => Support::from_bool(
+                getsockopt_len::<libc::xdp_mmap_offsets>(socket, XDP_MMAP_OFFSETS)?
+                    == size_of::<libc::xdp_mmap_offsets>(),
+            ),
+            Err(_) => at_least((5, 4)),
+        };
+
+        Ok(Self {
+            kernel_version,
+            af_xdp,
+            need_wakeup,
+            shared_umem: at_least((5, 10)),
+            multi_buffer: at_least((6, 6)),
+            hugepages: hugepage_pools(Path::new(HUGEPAGES_PATH))?,
+            interface: None,
+        })
+    }
+
+    /// Checks what the kernel and the driver of `interface_name` support.
+    ///
+    /// On Linux 6.3 and newer the driver reports its XDP features. Older
+    /// kernels are checked as `mode` allows, on `queue_id` for zero-copy. A
+    /// queue that another socket uses is reported as [`Support::Unknown`].
+    pub fn probe_interface(
+        interface_name: &str,
+        queue_id: u32,
+        mode: ProbeMode,
+    ) -> Result<Self, ProbeError> {
+        let mut capabilities = Self::probe()?;
+        capabilities.interface = Some(InterfaceCapabilities::probe(
+            interface_name,
+            queue_id,
+            capabilities.af_xdp,
+            mode,
+        )?);
+        Ok(capabilities)
+    }
+
+    /// Whether any hugepages are free to back a UMEM.
+    pub fn has_free_hugepages(&self) -> bool {
+        self.hugepages.iter().any(|pool| pool.available() > 0)
+    }
+}
+
+/// What the driver of an interface supports for XDP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InterfaceCapabilities {
+    pub interface_index: u32,
+    /// XDP programs in the driver rather than the generic SKB mode.
+    pub native_xdp: Support,
+    /// AF_XDP sockets in zero-copy mode.
+    pub zero_copy: Support,
+    /// XDP programs that receive frames spanning multiple buffers.
+    pub multi_buffer: Support,
+    /// The number of buffers that a zero-copy socket can chain into one
+    /// frame, or `None` when the driver does not say.
+    pub zero_copy_max_segments: Option<u32>,
+}
+
+impl InterfaceCapabilities {
+    fn probe(
+        interface_name: &str,
+        queue_id: u32,
+        af_xdp: Support,
+        mode: ProbeMode,
+    ) -> Result<Self, ProbeError> {
+        let name = CString::new(interface_name)
+            .map_err(|_| ProbeError::InterfaceNotFound(interface_name.to_owned()))?;
+        let interface_index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if interface_index == 0 {
+            return Err(ProbeError::InterfaceNotFound(interface_name.to_owned()));
+        }
+
+        match xdp_features(interface_index) {
+            Ok((features, zero_copy_max_segments)) => {
+                return Ok(Self::from_features(
+                    interface_index,
+                    features,
+                    zero_copy_max_segments,
+                ));
+            }
+            Err(NetlinkError::FamilyNotFound(_)) => {}
+            Err(error) => return Err(error.into()),
+        }
+
+        // Without the netdev family, the kernel predates multi-buffer.
+        let zero_copy = match (af_xdp, mode) {
+            (Support::Supported, ProbeMode::Attach) => bind_zero_copy(interface_index, queue_id)?,
+            (Support::Supported, ProbeMode::ReadOnly) => Support::Unknown,
+            (support, _) => support,
+        };
+        Ok(Self {
+            interface_index,
+            // Zero-copy needs a driver with native XDP.
+            native_xdp: match zero_copy {
+                Support::Supported => Support::Supported,
+                _ => native_xdp(interface_index, mode)?,
+            },
+            zero_copy,
+            multi_buffer: Support::Unsupported,
+            zero_copy_max_segments: None,
+        })
+    }
+
+    fn from_features(
+        interface_index: u32,
+        features: u64,
+        zero_copy_max_segments: Option<u32>,
+    ) -> Self {
+        Self {
+            interface_index,
+            native_xdp: Support::from_bool(features & NETDEV_XDP_ACT_BASIC != 0),
+            zero_copy: Support::from_bool(features & NETDEV_XDP_ACT_XSK_ZEROCOPY != 0),
+            multi_buffer: Support::from_bool(features & NETDEV_XDP_ACT_RX_SG != 0),
+            zero_copy_max_segments,
+        }
+    }
+}
+
+fn xdp_socket() -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::socket(AF_XDP, SOCK_RAW | SOCK_CLOEXEC, 0) };
+    if fd.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: The descriptor was just created and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Reads an `SOL_XDP` option into a `T` and returns the length that the
+/// kernel wrote.
+fn getsockopt_len<T>(socket: &OwnedFd, name: c_int) -> Result<usize, ProbeError> {
+    let mut value = std::mem::MaybeUninit::<T>::zeroed();
+    let mut length = size_of::<T>() as socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            SOL_XDP,
+            name,
+            value.as_mut_ptr().cast(),
+            &mut length,
+        )
+    };
+    if result.is_negative() {
+        return Err(ProbeError::Socket(io::Error::last_os_error()));
+    }
+    Ok(length as usize)
+}
+
+fn setsockopt<T>(socket: &OwnedFd, name: c_int, value: &T) -> io::Result<()> {
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            SOL_XDP,
+            name,
+            (value as *const T).cast(),
+            size_of::<T>() as socklen_t,
+        )
+    };
+    if result.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Binds a socket with a minimal UMEM in zero-copy mode and confirms the
+/// mode with `XDP_OPTIONS`.
+fn bind_zero_copy(interface_index: u32, queue_id: u32) -> Result<Support, ProbeError> {
+    const FRAME_SIZE: u32 = 4096;
+    const FRAME_COUNT: u32 = 4;
+
+    let socket = xdp_socket().map_err(ProbeError::Socket)?;
+    let length = (FRAME_SIZE * FRAME_COUNT) as usize;
+    let memory = unsafe {
+        libc::mmap(
+            null_mut(),
+            length,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if memory == libc::MAP_FAILED {
+        return Err(ProbeError::Socket(io::Error::last_os_error()));
+    }
+    let result = bind_umem(&socket, memory, length, interface_index, queue_id);
+    // The socket pins the UMEM until it is closed.
+    drop(socket);
+    unsafe { libc::munmap(memory, length) };
+
+    Ok(match result {
+        Ok(zero_copy) => Support::from_bool(zero_copy),
+        error => Support::from_result(&error),
+    })
+}
+
+fn bind_umem(
+    socket: &OwnedFd,
+    memory: *mut c_void,
+    length: usize,
+    interface_index: u32,
+    queue_id: u32,
+) -> io::Result<bool> {
+    let umem = libc::xdp_umem_reg_v1 {
+        addr: memory as u64,
+        len: length as u64,
+        chunk_size: 4096,
+        headroom: 0,
+    };
+    setsockopt(socket, XDP_UMEM_REG, &umem)?;
+    for ring in [XDP_UMEM_FILL_RING, XDP_UMEM_COMPLETION_RING, XDP_RX_RING] {
+        setsockopt(socket, ring, &4u32)?;
+    }
+
+    let address = libc::sockaddr_xdp {
+        sxdp_family: AF_XDP as u16,
+        sxdp_flags: XDP_ZEROCOPY,
+        sxdp_ifindex: interface_index,
+        sxdp_queue_id: queue_id,
+        sxdp_shared_umem_fd: 0,
+    };
+    let result = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            (&raw const address).cast(),
+            size_of::<libc::sockaddr_xdp>() as socklen_t,
+        )
+    };
+    if result.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+
+    // `XDP_OPTIONS` is Linux 5.3 and newer. A bind in zero-copy mode fails
+    // rather than falling back to copy mode, so success is enough.
+    let mut options = libc::xdp_options { flags: 0 };
+    let mut options_length = size_of::<libc::xdp_options>() as socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            SOL_XDP,
+            XDP_OPTIONS,
+            (&raw mut options).cast(),
+            &mut options_length,
+        )
+    };
+    Ok(result.is_negative() || options.flags & XDP_OPTIONS_ZEROCOPY != 0)
+}
+
+/// Checks whether the driver runs XDP programs natively, through
+/// `IFLA_XDP` of route netlink.
+fn native_xdp(interface_index: u32, mode: ProbeMode) -> Result<Support, ProbeError> {
+    let mut netlink = RouteNetlink::connect().map_err(ProbeError::Link)?;
+    let link = netlink
+        .get_link(interface_index)
+        .map_err(ProbeError::Link)?;
+    match xdp_attached(&link).map_err(ProbeError::Link)? {
+        Some(true) => return Ok(Support::Supported),
+        // A generic program keeps a native one from being attached.
+        Some(false) => return Ok(Support::Unknown),
+        None if mode == ProbeMode::ReadOnly => return Ok(Support::Unknown),
+        None => {}
+    }
+
+    // Loading a program needs `CAP_BPF` or `CAP_SYS_ADMIN`.
+    let Ok(program) = load_pass_program() else {
+        return Ok(Support::Unknown);
+    };
+    let mut attributes = AttributeWriter::default();
+    attributes.put_nested(IFLA_XDP, |xdp| {
+        xdp.put_u32(IFLA_XDP_FD, program.as_raw_fd() as u32);
+        xdp.put_u32(
+            IFLA_XDP_FLAGS,
+            XDP_FLAGS_DRV_MODE | XDP_FLAGS_UPDATE_IF_NOEXIST,
+        );
+    });
+    match netlink.set_link(interface_index, attributes) {
+        Ok(()) => {}
+        Err(NetlinkError::Kernel(error)) => return Ok(Support::from_result::<()>(&Err(error))),
+        Err(error) => return Err(ProbeError::Link(error)),
+    }
+
+    let mut attributes = AttributeWriter::default();
+    attributes.put_nested(IFLA_XDP, |xdp| {
+        xdp.put_u32(IFLA_XDP_FD, -1i32 as u32);
+        xdp.put_u32(IFLA_XDP_FLAGS, XDP_FLAGS_DRV_MODE);
+    });
+    netlink
+        .set_link(interface_index, attributes)
+        .map_err(ProbeError::Link)?;
+    Ok(Support::Supported)
+}
+
+/// Whether the program attached to a link runs natively, from the
+/// `IFLA_XDP` attribute of `RTM_GETLINK`. `None` when there is no program.
+fn xdp_attached(link: &[u8]) -> Result<Option<bool>, NetlinkError> {
+    let mut attached = None;
+    let mut native_program = false;
+    for attribute in Attributes(link) {
+        let attribute = attribute?;
+        if attribute.kind != IFLA_XDP {
+            continue;
+        }
+        for attribute in attribute.nested() {
+            let attribute = attribute?;
+            match attribute.kind {
+                IFLA_XDP_ATTACHED => attached = attribute.value.first().copied(),
+                IFLA_XDP_DRV_PROG_ID => native_program = true,
+                _ => {}
+            }
+        }
+    }
+    Ok(match attached {
+        Some(XDP_ATTACHED_DRV | XDP_ATTACHED_HW) => Some(true),
+        Some(XDP_ATTACHED_SKB) => Some(false),
+        Some(XDP_ATTACHED_MULTI) => Some(native_program),
+        _ => None,
+    })
+}
+
+/// One eBPF instruction, `struct bpf_insn`.
+#[repr(C)]
+struct BpfInstruction {
+    code: u8,
+    /// Destination register in the low nibble, source in the high one.
+    registers: u8,
+    offset: i16,
+    immediate: i32,
+}
+
+/// The fields of `union bpf_attr` that `BPF_PROG_LOAD` uses. The kernel
+/// treats the fields after them as zero.
+#[repr(C)]
+struct ProgramLoadAttributes {
+    program_type: u32,
+    instruction_count: u32,
+    instructions: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buffer: u64,
+    kernel_version: u32,
+    program_flags: u32,
+}
+
+/// Loads an XDP program that passes every packet.
+fn load_pass_program() -> io::Result<OwnedFd> {
+    const LICENSE: &CStr = c"GPL";
+    let instructions = [
+        // r0 = XDP_PASS
+        BpfInstruction {
+            code: 0xb7,
+            registers: 0,
+            offset: 0,
+            immediate: XDP_PASS,
+        },
+        // exit
+        BpfInstruction {
+            code: 0x95,
+            registers: 0,
+            offset: 0,
+            immediate: 0,
+        },
+    ];
+    let attributes = ProgramLoadAttributes {
+        program_type: BPF_PROG_TYPE_XDP,
+        instruction_count: instructions.len() as u32,
+        instructions: instructions.as_ptr() as u64,
+        license: LICENSE.as_ptr() as u64,
+        log_level: 0,
+        log_size: 0,
+        log_buffer: 0,
+        kernel_version: 0,
+        program_flags: 0,
+    };
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_LOAD,
+            &raw const attributes,
+            size_of::<ProgramLoadAttributes>(),
+        )
+    };
+    if fd.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: The descriptor was just created and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+/// Reads the XDP features and the zero-copy segment limit of the driver
+/// from the netdev generic netlink family, Linux 6.3 and newer.
+fn xdp_features(interface_index: u32) -> Result<(u64, Option<u32>), NetlinkError> {
+    let mut netlink = GenericNetlink::connect()?;
+    let family = netlink.family_id(NETDEV_FAMILY_NAME)?;
+    let mut attributes = AttributeWriter::default();
+    attributes.put_u32(NETDEV_A_DEV_IFINDEX, interface_index);
+    let reply = netlink.request(
+        family,
+        NETDEV_CMD_DEV_GET,
+        NETDEV_FAMILY_VERSION,
+        attributes,
+    )?;
+    parse_xdp_features(&reply)
+}
+
+fn parse_xdp_features(reply: &[u8]) -> Result<(u64, Option<u32>), NetlinkError> {
+    let mut features = None;
+    let mut zero_copy_max_segments = None;
+    for attribute in Attributes(reply) {
+        let attribute = attribute?;
+        match attribute.kind {
+            NETDEV_A_DEV_XDP_FEATURES => features = Some(attribute.as_u64()?),
+            NETDEV_A_DEV_XDP_ZC_MAX_SEGS => zero_copy_max_segments = Some(attribute.as_u32()?),
+            _ => {}
+        }
+    }
+    let features = features.ok_or(NetlinkError::MissingAttribute("NETDEV_A_DEV_XDP_FEATURES"))?;
+    Ok((features, zero_copy_max_segments))
+}
+
+/// Reads the hugepage pools, e.g. `hugepages-2048kB`. A kernel without
+/// hugepages has no pools.
+fn hugepage_pools(root: &Path) -> Result<Vec<HugepagePool>, ProbeError> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(ProbeError::Hugepages(error)),
+    };
+
+    let mut pools = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(ProbeError::Hugepages)?;
+        let Some(size) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("hugepages-"))
+            .and_then(|size| size.strip_suffix("kB"))
+            .and_then(|size| size.parse::<usize>().ok())
+        else {
+            continue;
+        };
+
+        pools.push(read_pool(&entry.path(), size * 1024)?);
+    }
+    pools.sort_by_key(|pool| pool.size);
+
+    Ok(pools)
+}
+
+/// Reads a pool directory. The directories of the NUMA nodes have no
+/// `resv_hugepages`.
+fn read_pool(path: &Path, size: usize) -> Result<HugepagePool, ProbeError> {
+    let read_count = |name: &str| -> Result<u64, ProbeError> {
+        let count = fs::read_to_string(path.join(name)).map_err(ProbeError::Hugepages)?;
+        count
+            .trim()
+            .parse()
+            .map_err(|_| ProbeError::Hugepages(io::Error::from(io::ErrorKind::InvalidData)))
+    };
+
+    Ok(HugepagePool {
+        size,
+        total: read_count("nr_hugepages")?,
+        free: read_count("free_hugepages")?,
+        reserved: match path.join("resv_hugepages").exists() {
+            true => read_count("resv_hugepages")?,
+            false => 0,
+        },
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProbeError {
+    #[error("{0}")]
+    SystemInfo(#[from] SystemInfoError),
+    #[error("AF_XDP socket error: {0}")]
+    Socket(io::Error),
+    #[error("Failed to read the hugepage pools: {0}")]
+    Hugepages(io::Error),
+    #[error("Network interface not found: {0}")]
+    InterfaceNotFound(String),
+    #[error("Failed to read the XDP features of the driver: {0}")]
+    XdpFeatures(#[from] NetlinkError),
+    #[error("Failed to check XDP on the link: {0}")]
+    Link(NetlinkError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xdp_features_reply() {
+        let mut reply = AttributeWriter::default();
+        reply.put_u32(NETDEV_A_DEV_IFINDEX, 2);
+        reply.put(
+            NETDEV_A_DEV_XDP_FEATURES,
+            &(NETDEV_XDP_ACT_BASIC | NETDEV_XDP_ACT_XSK_ZEROCOPY).to_ne_bytes(),
+        );
+        reply.put_u32(NETDEV_A_DEV_XDP_ZC_MAX_SEGS, 1);
+
+        let (features, segments) = parse_xdp_features(reply.as_bytes()).unwrap();
+        let interface = InterfaceCapabilities::from_features(2, features, segments);
+        assert_eq!(interface.native_xdp, Support::Supported);
+        assert_eq!(interface.zero_copy, Support::Supported);
+        assert_eq!(interface.multi_buffer, Support::Unsupported);
+        assert_eq!(interface.zero_copy_max_segments, Some(1));
+
+        assert!(matches!(
+            parse_xdp_features(&reply.as_bytes()[..8]),
+            Err(NetlinkError::MissingAttribute(_))
+        ));
+    }
+
+    #[test]
+    fn xdp_attached_reply() {
+        let link = |attached: u8, native_program: bool| {
+            let mut reply = AttributeWriter::default();
+            // IFLA_IFNAME
+            reply.put_str(3, "eth0");
+            reply.put_nested(IFLA_XDP, |xdp| {
+                xdp.put(IFLA_XDP_ATTACHED, &[attached]);
+                if native_program {
+                    xdp.put_u32(IFLA_XDP_DRV_PROG_ID, 7);
+                }
+            });
+            xdp_attached(reply.as_bytes()).unwrap()
+        };
+        assert_eq!(link(0, false), None);
+        assert_eq!(link(XDP_ATTACHED_DRV, true), Some(true));
+        assert_eq!(link(XDP_ATTACHED_SKB, false), Some(false));
+        assert_eq!(link(XDP_ATTACHED_MULTI, false), Some(false));
+        assert_eq!(link(XDP_ATTACHED_MULTI, true), Some(true));
+        assert_eq!(xdp_attached(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn hugepages() {
+        let root = std::env::temp_dir().join(format!("mangonel-hugepages-{}", std::process::id()));
+        for (pool, total, free, reserved) in [
+            // Like the pools of a NUMA node, without `resv_hugepages`.
+            ("hugepages-1048576kB", "0\n", "0\n", None),
+            ("hugepages-2048kB", "64\n", "60\n", Some("8\n")),
+        ] {
+            fs::create_dir_all(root.join(pool)).unwrap();
+            fs::write(root.join(pool).join("nr_hugepages"), total).unwrap();
+            fs::write(root.join(pool).join("free_hugepages"), free).unwrap();
+            if let Some(reserved) = reserved {
+                fs::write(root.join(pool).join("resv_hugepages"), reserved).unwrap();
+            }
+        }
+
+        let pools = hugepage_pools(&root);
+        fs::remove_dir_all(&root).unwrap();
+        let pools = pools.unwrap();
+        assert_eq!(
+            pools,
+            [
+                HugepagePool {
+                    size: 2 << 20,
+                    total: 64,
+                    free: 60,
+                    reserved: 8,
+                },
+                HugepagePool {
+                    size: 1 << 30,
+                    total: 0,
+                    free: 0,
+                    reserved: 0,
+                },
+            ]
+        );
+        assert_eq!(pools[0].available(), 52);
+        assert!(
+            hugepage_pools(Path::new("/nonexistent"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn probe() {
+        let capabilities = Capabilities::probe().unwrap();
+        println!("{capabilities:?}");
+        if capabilities.af_xdp.is_supported() && capabilities.kernel_version >= (5, 4) {
+            assert!(capabilities.need_wakeup.is_supported());
+        }
+        assert!(matches!(
+            Capabilities::probe_interface("mangonel-none", 0, ProbeMode::ReadOnly),
+            Err(ProbeError::InterfaceNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "binds sockets and attaches XDP programs on lo"]
+    fn probe_loopback() {
+        // Loopback has no zero-copy support, so binding does not touch it.
+        if Capabilities::probe().unwrap().af_xdp.is_supported() {
+            assert_ne!(bind_zero_copy(1, 0).unwrap(), Support::Supported);
+        }
+        for mode in [ProbeMode::ReadOnly, ProbeMode::Attach] {
+            match Capabilities::probe_interface("lo", 0, mode) {
+                Ok(capabilities) => println!("{mode:?}: {:?}", capabilities.interface),
+                Err(error) => println!("Could not probe lo: {error}"),
+            }
+        }
+    }
+}
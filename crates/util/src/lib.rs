@@ -1 +1,4 @@
+pub mod capability;
+pub mod netlink;
+pub mod numa;
 pub mod system;
@@ -0,0 +1,507 @@
+//! A minimal netlink client, enough to talk to the ethtool and netdev
+//! generic netlink families and to get and set links over route netlink.
+//!
+//! Netlink headers and attributes are in host byte order.
+
+use libc::{
+    AF_NETLINK, AF_UNSPEC, NETLINK_GENERIC, NETLINK_ROUTE, NLM_F_ACK, NLM_F_REQUEST, NLMSG_ERROR,
+    RTM_GETLINK, RTM_NEWLINK, RTM_SETLINK, SOCK_CLOEXEC, SOCK_RAW, c_int, sockaddr, sockaddr_nl,
+};
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+const IFINFOMSG_LEN: usize = 16;
+const NLA_HDRLEN: usize = 4;
+const NLA_F_NESTED: u16 = 1 << 15;
+const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+/// A generic netlink socket.
+#[derive(Debug)]
+pub struct GenericNetlink(Socket);
+
+impl GenericNetlink {
+    pub fn connect() -> Result<Self, NetlinkError> {
+        Socket::connect(NETLINK_GENERIC).map(Self)
+    }
+
+    /// Looks up the ID of a generic netlink family by name.
+    pub fn family_id(&mut self, name: &str) -> Result<u16, NetlinkError> {
+        let mut attributes = AttributeWriter::default();
+        attributes.put_str(CTRL_ATTR_FAMILY_NAME, name);
+        let reply = self
+            .request(GENL_ID_CTRL, CTRL_CMD_GETFAMILY, 1, attributes)
+            .map_err(|error| match error {
+                NetlinkError::Kernel(error) if error.raw_os_error() == Some(libc::ENOENT) => {
+                    NetlinkError::FamilyNotFound(name.to_owned())
+                }
+                error => error,
+            })?;
+        parse_family_id(&reply)
+    }
+
+    /// Sends a request and returns the attributes of the reply. Requests
+    /// without a reply return no attributes once the kernel acknowledged
+    /// them.
+    pub fn request(
+        &mut self,
+        family: u16,
+        command: u8,
+        version: u8,
+        attributes: AttributeWriter,
+    ) -> Result<Vec<u8>, NetlinkError> {
+        let header: [u8; GENL_HDRLEN] = [command, version, 0, 0];
+        self.0
+            .request(family, &header, attributes.as_bytes(), family)
+    }
+}
+
+/// A route netlink socket for the links, i.e. the network interfaces.
+#[derive(Debug)]
+pub struct RouteNetlink(Socket);
+
+impl RouteNetlink {
+    pub fn connect() -> Result<Self, NetlinkError> {
+        Socket::connect(NETLINK_ROUTE).map(Self)
+    }
+
+    /// Returns the `IFLA_*` attributes of the link with `interface_index`.
+    pub fn get_link(&mut self, interface_index: u32) -> Result<Vec<u8>, NetlinkError> {
+        self.0
+            .request(RTM_GETLINK, &link_header(interface_index), &[], RTM_NEWLINK)
+    }
+
+    /// Changes the link with `interface_index` with `IFLA_*` attributes.
+    pub fn set_link(
+        &mut self,
+        interface_index: u32,
+        attributes: AttributeWriter,
+    ) -> Result<(), NetlinkError> {
+        self.0
+            .request(
+                RTM_SETLINK,
+                &link_header(interface_index),
+                attributes.as_bytes(),
+                RTM_NEWLINK,
+            )
+            .map(drop)
+    }
+}
+
+/// A `struct ifinfomsg` that selects a link by index.
+fn link_header(interface_index: u32) -> [u8; IFINFOMSG_LEN] {
+    let mut header = [0; IFINFOMSG_LEN];
+    header[0] = AF_UNSPEC as u8;
+    header[4..8].copy_from_slice(&interface_index.to_ne_bytes());
+    header
+}
+
+/// A bound netlink socket of any protocol.
+#[derive(Debug)]
+struct Socket {
+    fd: OwnedFd,
+    sequence: u32,
+}
+
+impl Socket {
+    fn connect(protocol: c_int) -> Result<Self, NetlinkError> {
+        let fd = unsafe { libc::socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, protocol) };
+        if fd.is_negative() {
+            return Err(NetlinkError::Socket(io::Error::last_os_error()));
+        }
+        // SAFETY: The descriptor was just created and nothing else owns it.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // The kernel assigns the port ID when the socket is bound.
+        let mut address = unsafe { std::mem::zeroed::<sockaddr_nl>() };
+        address.nl_family = AF_NETLINK as u16;
+        let value = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                (&raw const address).cast::<sockaddr>(),
+                size_of::<sockaddr_nl>() as u32,
+            )
+        };
+        if value.is_negative() {
+            return Err(NetlinkError::Socket(io::Error::last_os_error()));
+        }
+
+        Ok(Self { fd, sequence: 0 })
+    }
+
+    /// Sends a request of `kind` and returns the attributes of the reply of
+    /// `reply_kind`. `header` is the header of the protocol, such as
+    /// `struct genlmsghdr`, and the reply starts with a header of the same
+    /// length. Requests without a reply return no attributes once the kernel
+    /// acknowledged them.
+    fn request(
+        &mut self,
+        kind: u16,
+        header: &[u8],
+        attributes: &[u8],
+        reply_kind: u16,
+    ) -> Result<Vec<u8>, NetlinkError> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let message = encode_request(kind, self.sequence, header, attributes);
+        let length = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                message.as_ptr().cast(),
+                message.len(),
+                0,
+            )
+        };
+        if length.is_negative() {
+            return Err(NetlinkError::Socket(io::Error::last_os_error()));
+        }
+
+        let mut buffer = vec![0u8; 1 << 16];
+        let mut reply = Vec::new();
+        loop {
+            let length = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    0,
+                )
+            };
+            if length.is_negative() {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(NetlinkError::Socket(error));
+            }
+
+            for message in Messages(&buffer[..length as usize]) {
+                let message = message?;
+                if message.sequence != self.sequence {
+                    continue;
+                }
+                match message.kind {
+                    // The acknowledgment comes last, after any reply.
+                    kind if kind == NLMSG_ERROR as u16 => {
+                        return match read_i32(message.payload)? {
+                            0 => Ok(reply),
+                            error => {
+                                Err(NetlinkError::Kernel(io::Error::from_raw_os_error(-error)))
+                            }
+                        };
+                    }
+                    kind if kind == reply_kind => {
+                        reply = message
+                            .payload
+                            .get(header.len()..)
+                            .ok_or(NetlinkError::Malformed)?
+                            .to_vec();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Encodes a netlink request that asks for an acknowledgment.
+fn encode_request(kind: u16, sequence: u32, header: &[u8], attributes: &[u8]) -> Vec<u8> {
+    let length = NLMSG_HDRLEN + header.len() + attributes.len();
+    let mut message = Vec::with_capacity(length);
+    message.extend_from_slice(&(length as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(&((NLM_F_REQUEST | NLM_F_ACK) as u16).to_ne_bytes());
+    message.extend_from_slice(&sequence.to_ne_bytes());
+    // The kernel fills in the port ID.
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(header);
+    message.extend_from_slice(attributes);
+    message
+}
+
+fn parse_family_id(reply: &[u8]) -> Result<u16, NetlinkError> {
+    Attributes(reply)
+        .find_map(|attribute| match attribute {
+            Ok(attribute) if attribute.kind == CTRL_ATTR_FAMILY_ID => Some(attribute.as_u16()),
+            Ok(_) => None,
+            Err(error) => Some(Err(error)),
+        })
+        .ok_or(NetlinkError::MissingAttribute("CTRL_ATTR_FAMILY_ID"))?
+}
+
+/// A netlink message in a receive buffer.
+#[derive(Debug)]
+struct Message<'a> {
+    kind: u16,
+    sequence: u32,
+    payload: &'a [u8],
+}
+
+/// Iterates over the netlink messages in a receive buffer.
+struct Messages<'a>(&'a [u8]);
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<Message<'a>, NetlinkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let buffer = self.0;
+        let length = match buffer.get(..4) {
+            Some(bytes) => u32::from_ne_bytes(bytes.try_into().unwrap()) as usize,
+            None => 0,
+        };
+        if length < NLMSG_HDRLEN || length > buffer.len() {
+            self.0 = &[];
+            return Some(Err(NetlinkError::Malformed));
+        }
+
+        self.0 = &buffer[align(length).min(buffer.len())..];
+        Some(Ok(Message {
+            kind: u16::from_ne_bytes([buffer[4], buffer[5]]),
+            sequence: u32::from_ne_bytes(buffer[8..12].try_into().unwrap()),
+            payload: &buffer[NLMSG_HDRLEN..length],
+        }))
+    }
+}
+
+/// A netlink attribute.
+#[derive(Clone, Copy, Debug)]
+pub struct Attribute<'a> {
+    /// Type without the nested and byte order flags.
+    pub kind: u16,
+    pub value: &'a [u8],
+}
+
+impl<'a> Attribute<'a> {
+    #[inline]
+    pub fn as_u16(&self) -> Result<u16, NetlinkError> {
+        Ok(u16::from_ne_bytes(self.value_array()?))
+    }
+
+    #[inline]
+    pub fn as_u32(&self) -> Result<u32, NetlinkError> {
+        Ok(u32::from_ne_bytes(self.value_array()?))
+    }
+
+    #[inline]
+    pub fn as_u64(&self) -> Result<u64, NetlinkError> {
+        Ok(u64::from_ne_bytes(self.value_array()?))
+    }
+
+    /// A NUL-terminated string.
+    pub fn as_str(&self) -> Result<&'a str, NetlinkError> {
+        let value = self.value.strip_suffix(&[0]).unwrap_or(self.value);
+        std::str::from_utf8(value).map_err(|_| NetlinkError::Malformed)
+    }
+
+    /// The attributes of a nested attribute.
+    #[inline]
+    pub fn nested(&self) -> Attributes<'a> {
+        Attributes(self.value)
+    }
+
+    #[inline]
+    fn value_array<const N: usize>(&self) -> Result<[u8; N], NetlinkError> {
+        self.value.try_into().map_err(|_| NetlinkError::Malformed)
+    }
+}
+
+/// Iterates over a stream of netlink attributes.
+#[derive(Clone, Copy, Debug)]
+pub struct Attributes<'a>(pub &'a [u8]);
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = Result<Attribute<'a>, NetlinkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let buffer = self.0;
+        let length = match buffer.get(..2) {
+            Some(bytes) => u16::from_ne_bytes([bytes[0], bytes[1]]) as usize,
+            None => 0,
+        };
+        if length < NLA_HDRLEN || length > buffer.len() {
+            self.0 = &[];
+            return Some(Err(NetlinkError::Malformed));
+        }
+
+        self.0 = &buffer[align(length).min(buffer.len())..];
+        let kind = u16::from_ne_bytes([buffer[2], buffer[3]]);
+        Some(Ok(Attribute {
+            kind: kind & !(NLA_F_NESTED | NLA_F_NET_BYTEORDER),
+            value: &buffer[NLA_HDRLEN..length],
+        }))
+    }
+}
+
+/// Builds a stream of netlink attributes.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeWriter(Vec<u8>);
+
+impl AttributeWriter {
+    pub fn put(&mut self, kind: u16, value: &[u8]) {
+        let length = NLA_HDRLEN + value.len();
+        self.0.extend_from_slice(&(length as u16).to_ne_bytes());
+        self.0.extend_from_slice(&kind.to_ne_bytes());
+        self.0.extend_from_slice(value);
+        self.0.resize(align(self.0.len()), 0);
+    }
+
+    /// Puts a flag, which is an attribute without a value.
+    #[inline]
+    pub fn put_flag(&mut self, kind: u16) {
+        self.put(kind, &[]);
+    }
+
+    #[inline]
+    pub fn put_u32(&mut self, kind: u16, value: u32) {
+        self.put(kind, &value.to_ne_bytes());
+    }
+
+    /// Puts a NUL-terminated string.
+    pub fn put_str(&mut self, kind: u16, value: &str) {
+        let mut bytes = Vec::with_capacity(value.len() + 1);
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.push(0);
+        self.put(kind, &bytes);
+    }
+
+    /// Puts a nested attribute whose attributes `build` writes.
+    pub fn put_nested(&mut self, kind: u16, build: impl FnOnce(&mut Self)) {
+        let mut nested = Self::default();
+        build(&mut nested);
+        self.put(kind | NLA_F_NESTED, &nested.0);
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Rounds up to the 4-byte alignment of netlink messages and attributes.
+#[inline]
+fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+#[inline]
+fn read_i32(payload: &[u8]) -> Result<i32, NetlinkError> {
+    payload
+        .get(..4)
+        .map(|bytes| i32::from_ne_bytes(bytes.try_into().unwrap()))
+        .ok_or(NetlinkError::Malformed)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NetlinkError {
+    #[error("Netlink socket error: {0}")]
+    Socket(io::Error),
+    #[error("The kernel rejected the request: {0}")]
+    Kernel(io::Error),
+    #[error("Generic netlink family {0} is not available")]
+    FamilyNotFound(String),
+    #[error("Malformed netlink message")]
+    Malformed,
+    #[error("Netlink reply has no {0} attribute")]
+    MissingAttribute(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reply to CTRL_CMD_GETFAMILY for "ethtool", recorded on x86_64 and cut
+    /// after the first few attributes.
+    const FAMILY_REPLY: [u8; 64] = [
+        0x40, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xa5, 0x3c, 0x00,
+        0x00, 0x01, 0x02, 0x00, 0x00, 0x0c, 0x00, 0x02, 0x00, 0x65, 0x74, 0x68, 0x74, 0x6f, 0x6f,
+        0x6c, 0x00, 0x06, 0x00, 0x01, 0x00, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x05, 0x00,
+        0x2e, 0x00, 0x00, 0x00,
+    ];
+
+    /// An acknowledgment, followed by an error for a later request.
+    const ACK_AND_ERROR: [u8; 72] = [
+        0x24, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0xa5, 0x3c, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x14, 0x00, 0x05, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x01, 0x03,
+        0x00, 0x00, 0x00, 0xa5, 0x3c, 0x00, 0x00, 0xea, 0xff, 0xff, 0xff, 0x14, 0x00, 0x00, 0x00,
+        0x14, 0x00, 0x05, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn family_reply() {
+        let mut messages = Messages(&FAMILY_REPLY);
+        let message = messages.next().unwrap().unwrap();
+        assert!(messages.next().is_none());
+        assert_eq!(message.kind, GENL_ID_CTRL);
+        assert_eq!(message.sequence, 1);
+
+        let attributes = &message.payload[GENL_HDRLEN..];
+        let name = Attributes(attributes).next().unwrap().unwrap();
+        assert_eq!(name.kind, CTRL_ATTR_FAMILY_NAME);
+        assert_eq!(name.value, b"ethtool\0");
+        assert_eq!(parse_family_id(attributes).unwrap(), 0x14);
+        assert!(matches!(
+            parse_family_id(&attributes[..12]),
+            Err(NetlinkError::MissingAttribute(_))
+        ));
+    }
+
+    #[test]
+    fn acknowledgments() {
+        let errors: Vec<_> = Messages(&ACK_AND_ERROR)
+            .map(|message| {
+                let message = message.unwrap();
+                assert_eq!(message.kind, NLMSG_ERROR as u16);
+                (message.sequence, read_i32(message.payload).unwrap())
+            })
+            .collect();
+        assert_eq!(errors, [(2, 0), (3, -libc::EINVAL)]);
+        assert!(matches!(
+            Messages(&ACK_AND_ERROR[..20]).next(),
+            Some(Err(NetlinkError::Malformed))
+        ));
+    }
+
+    #[test]
+    fn encode() {
+        let mut attributes = AttributeWriter::default();
+        attributes.put_nested(1, |header| header.put_u32(1, 2));
+        attributes.put_u32(2, 7);
+        attributes.put_str(3, "eth0");
+        attributes.put_flag(4);
+
+        let message = encode_request(0x14, 9, &[18, 1, 0, 0], attributes.as_bytes());
+        assert_eq!(message.len(), 16 + 4 + 12 + 8 + 12 + 4);
+        assert_eq!(&message[..4], &(message.len() as u32).to_ne_bytes());
+        assert_eq!(&message[16..20], &[18, 1, 0, 0]);
+
+        let parsed: Vec<_> = Attributes(attributes.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(parsed[0].kind, 1);
+        let nested = parsed[0].nested().next().unwrap().unwrap();
+        assert_eq!(nested.as_u32().unwrap(), 2);
+        assert_eq!(parsed[1].as_u32().unwrap(), 7);
+        assert_eq!(parsed[2].as_str().unwrap(), "eth0");
+        assert_eq!(parsed[3].kind, 4);
+        assert!(parsed[3].value.is_empty());
+
+        let message = encode_request(RTM_GETLINK, 1, &link_header(3), &[]);
+        assert_eq!(message.len(), 16 + 16);
+        assert_eq!(&message[20..24], &3u32.to_ne_bytes());
+    }
+}
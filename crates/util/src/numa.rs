@@ -0,0 +1,69 @@
+//! NUMA memory policies for UMEM memory and worker threads.
+
+use libc::c_void;
+use std::io;
+
+/// Binds `length` bytes at `address` to `node` with `mbind(MPOL_BIND)`.
+/// Pages that are allocated later only come from that node.
+///
+/// # Safety
+///
+/// `address` and `length` must describe a mapping that the caller owns.
+pub unsafe fn bind_memory(address: *mut c_void, length: usize, node: u32) -> io::Result<()> {
+    let (node_mask, max_node) = node_mask(node);
+    let value = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            address,
+            length,
+            libc::MPOL_BIND,
+            node_mask.as_ptr(),
+            max_node,
+            0,
+        )
+    };
+    if value.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Makes the kernel prefer `node` for the memory the calling thread
+/// allocates from now on, with `set_mempolicy(MPOL_PREFERRED)`.
+pub fn prefer_node(node: u32) -> io::Result<()> {
+    let (node_mask, max_node) = node_mask(node);
+    let value = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            libc::MPOL_PREFERRED,
+            node_mask.as_ptr(),
+            max_node,
+        )
+    };
+    if value.is_negative() {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// A node mask with only `node` set, and the `maxnode` argument for it.
+fn node_mask(node: u32) -> (Vec<u64>, usize) {
+    let mut node_mask = vec![0u64; node as usize / 64 + 1];
+    node_mask[node as usize / 64] |= 1 << (node % 64);
+    // The kernel reads one bit less than `maxnode`.
+    let max_node = node_mask.len() * 64 + 1;
+    (node_mask, max_node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask() {
+        assert_eq!(node_mask(0), (vec![1], 65));
+        assert_eq!(node_mask(65), (vec![0, 2], 129));
+    }
+}
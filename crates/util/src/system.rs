@@ -15,6 +15,18 @@ pub fn check_system_info() -> Result<(), SystemInfoError> {
     }
 
     // Check kernel version.
+    let (major, minor) = kernel_version()?;
+
+    // Requires kernel version 5.10 or newer.
+    if (major, minor) < (5, 10) {
+        return Err(SystemInfoError::UnsupportedKernelVersion { major, minor });
+    }
+
+    Ok(())
+}
+
+/// Returns the major and minor version of the running kernel.
+pub fn kernel_version() -> Result<(u32, u32), SystemInfoError> {
     let mut utsname = unsafe { std::mem::zeroed::<libc::utsname>() };
     if unsafe { libc::uname(&mut utsname) } != 0 {
         return Err(SystemInfoError::KernelVersionNotFound);
@@ -34,12 +46,7 @@ pub fn check_system_info() -> Result<(), SystemInfoError> {
         .and_then(|s| s.parse().ok())
         .ok_or(SystemInfoError::ParseKernelVersion)?;
 
-    // Requires kernel version 5.10 or newer.
-    if (major, minor) < (5, 10) {
-        return Err(SystemInfoError::UnsupportedKernelVersion { major, minor });
-    }
-
-    Ok(())
+    Ok((major, minor))
 }
 
 /// Returns the default shared library search paths on Linux.
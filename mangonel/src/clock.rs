@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+/// Where a [`Clock`] reads the time from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockSource {
+    /// The TSC when it is invariant, `CLOCK_MONOTONIC_RAW` otherwise.
+    #[default]
+    Auto,
+    /// `CLOCK_MONOTONIC_RAW`, which NTP does not slew.
+    MonotonicRaw,
+    /// The time stamp counter, calibrated against `CLOCK_MONOTONIC_RAW`.
+    /// Requires an invariant TSC on x86_64.
+    Tsc,
+}
+
+/// A monotonic nanosecond clock for pacing packets.
+///
+/// Reading the TSC costs a few nanoseconds, which is what makes
+/// sub-microsecond spacing possible; a `clock_gettime` call costs tens.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    source: ClockSource,
+    origin: u64,
+    /// Nanoseconds per TSC tick. Unused for `CLOCK_MONOTONIC_RAW`.
+    nanos_per_tick: f64,
+}
+
+impl Clock {
+    /// How long the TSC is measured against `CLOCK_MONOTONIC_RAW`.
+    const CALIBRATION_TIME: Duration = Duration::from_millis(20);
+
+    /// Creates a clock. Creating a TSC clock blocks for the calibration,
+    /// about 20 ms.
+    pub fn new(source: ClockSource) -> Result<Self, ClockError> {
+        let source = match source {
+            ClockSource::Auto if tsc::is_invariant() => ClockSource::Tsc,
+            ClockSource::Auto => ClockSource::MonotonicRaw,
+            ClockSource::Tsc if !tsc::is_invariant() => return Err(ClockError::TscUnavailable),
+            source => source,
+        };
+
+        match source {
+            ClockSource::Tsc => {
+                let nanos_per_tick = tsc::calibrate(Self::CALIBRATION_TIME)?;
+                Ok(Self {
+                    source,
+                    origin: tsc::read(),
+                    nanos_per_tick,
+                })
+            }
+            _ => Ok(Self {
+                source,
+                origin: monotonic_raw()?,
+                nanos_per_tick: 1.0,
+            }),
+        }
+    }
+
+    /// The source that was picked, never [`ClockSource::Auto`].
+    #[inline]
+    pub fn source(&self) -> ClockSource {
+        self.source
+    }
+
+    /// Nanoseconds since the clock was created.
+    #[inline]
+    pub fn now(&self) -> u64 {
+        match self.source {
+            ClockSource::Tsc => {
+                (tsc::read().wrapping_sub(self.origin) as f64 * self.nanos_per_tick) as u64
+            }
+            // The clock was read successfully when it was created, and
+            // `clock_gettime` does not fail for a valid clock ID after that.
+            _ => monotonic_raw().unwrap_or(self.origin) - self.origin,
+        }
+    }
+
+    /// Waits until [`Clock::now`] reaches `deadline`. Long waits sleep
+    /// first, and the last stretch is spun for precision.
+    pub fn wait_until(&self, deadline: u64) {
+        const SPIN_TIME: u64 = 50_000;
+
+        let now = self.now();
+        if deadline > now + SPIN_TIME {
+            std::thread::sleep(Duration::from_nanos(deadline - now - SPIN_TIME));
+        }
+        while self.now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+fn monotonic_raw() -> Result<u64, ClockError> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let value = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC_RAW, &mut time) };
+    if value.is_negative() {
+        return Err(ClockError::ClockGettime(std::io::Error::last_os_error()));
+    }
+
+    Ok(time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod tsc {
+    use super::{ClockError, monotonic_raw};
+    use std::{arch::x86_64, time::Duration};
+
+    /// Checks the invariant TSC flag, CPUID leaf 0x80000007 EDX bit 8. An
+    /// invariant TSC ticks at a constant rate in every P-, C- and T-state.
+    // `__cpuid` is only safe to call on newer toolchains than the MSRV.
+    #[allow(unused_unsafe)]
+    pub(super) fn is_invariant() -> bool {
+        let highest_leaf = unsafe { x86_64::__cpuid(0x8000_0000) }.eax;
+        if highest_leaf < 0x8000_0007 {
+            return false;
+        }
+        unsafe { x86_64::__cpuid(0x8000_0007) }.edx & (1 << 8) != 0
+    }
+
+    #[inline]
+    pub(super) fn read() -> u64 {
+        unsafe { x86_64::_rdtsc() }
+    }
+
+    pub(super) fn calibrate(duration: Duration) -> Result<f64, ClockError> {
+        let start_nanos = monotonic_raw()?;
+        let start_ticks = read();
+        std::thread::sleep(duration);
+        let end_nanos = monotonic_raw()?;
+        let end_ticks = read();
+
+        let ticks = end_ticks.wrapping_sub(start_ticks);
+        if ticks == 0 {
+            return Err(ClockError::TscUnavailable);
+        }
+        Ok((end_nanos - start_nanos) as f64 / ticks as f64)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod tsc {
+    use super::ClockError;
+    use std::time::Duration;
+
+    pub(super) fn is_invariant() -> bool {
+        false
+    }
+
+    pub(super) fn read() -> u64 {
+        0
+    }
+
+    pub(super) fn calibrate(_: Duration) -> Result<f64, ClockError> {
+        Err(ClockError::TscUnavailable)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClockError {
+    #[error("The CPU has no invariant TSC")]
+    TscUnavailable,
+    #[error("Failed to read CLOCK_MONOTONIC_RAW: {0}")]
+    ClockGettime(std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monotonic() {
+        for source in [ClockSource::Auto, ClockSource::MonotonicRaw] {
+            let clock = Clock::new(source).unwrap();
+            assert_ne!(clock.source(), ClockSource::Auto);
+
+            let start = clock.now();
+            clock.wait_until(start + 200_000);
+            let elapsed = clock.now() - start;
+            assert!(elapsed >= 200_000, "{elapsed}");
+            assert!(elapsed < 50_000_000, "{elapsed}");
+        }
+    }
+}
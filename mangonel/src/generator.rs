@@ -0,0 +1,495 @@
+use crate::{
+    clock::{Clock, ClockSource},
+    shaper::{BurstProfile, Rate, RateController, RateControllerBuilder, ShaperError},
+    template::{FieldVariation, FlowTemplate, TemplateError},
+};
+use mangonel_libxdp::{Descriptor, SocketBuilder, SocketError, TxSocket, Umem};
+use mangonel_packet::{PacketBuilder, PacketError};
+use mangonel_thread::ThreadError;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// When a generator stops on its own, besides [`Generator::stop`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Limit {
+    #[default]
+    Unlimited,
+    /// Total number of packets over all queues.
+    Packets(u64),
+    Duration(Duration),
+}
+
+/// A TX queue and the core that its worker is pinned to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueAssignment {
+    pub queue_id: u32,
+    pub core_id: usize,
+}
+
+#[derive(Debug)]
+pub struct GeneratorBuilder {
+    /// Used to build one socket per queue.
+    ///
+    /// Defaults to [`SocketBuilder::inhibit_program_load`], because the
+    /// generator never fills the RX rings. With libxdp's redirect program
+    /// attached, every packet arriving on a generator queue would be
+    /// redirected to a socket with an empty fill ring and dropped.
+    pub socket_builder: SocketBuilder,
+    /// The packet sent by every worker.
+    pub template: PacketBuilder,
+    /// Fields of the template that change from packet to packet. Bit rates
+    /// are paced by the length of the unmodified template.
+    pub variations: Vec<FieldVariation>,
+    /// Target rate summed over all queues. Each worker paces its share.
+    pub rate: Rate,
+    pub profile: BurstProfile,
+    pub clock_source: ClockSource,
+    /// Seed for [`BurstProfile::Poisson`] and random field values. Worker
+    /// `i` uses `seed + i`.
+    /// Seeded from the operating system when `None`.
+    pub seed: Option<u64>,
+    pub limit: Limit,
+    /// One TX worker is spawned per queue.
+    pub queues: Vec<QueueAssignment>,
+    /// Maximum number of packets submitted per [`TxSocket::write`].
+    pub batch_size: u32,
+    /// How long to wait for the kernel to complete outstanding frames
+    /// after the generator stops.
+    pub drain_timeout: Duration,
+}
+
+impl Default for GeneratorBuilder {
+    fn default() -> Self {
+        Self {
+            socket_builder: SocketBuilder {
+                inhibit_program_load: true,
+                ..Default::default()
+            },
+            template: PacketBuilder::default(),
+            variations: Vec::new(),
+            rate: Rate::Unlimited,
+            profile: BurstProfile::Constant,
+            clock_source: ClockSource::Auto,
+            seed: None,
+            limit: Limit::Unlimited,
+            queues: vec![QueueAssignment {
+                queue_id: 0,
+                core_id: 0,
+            }],
+            batch_size: 64,
+            drain_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+impl GeneratorBuilder {
+    /// Builds a socket per queue and starts the workers.
+    ///
+    /// Every socket is built before any worker starts, so a bad queue or
+    /// a template that does not fit into a frame fails without sending
+    /// anything.
+    pub fn spawn(self, interface_name: impl AsRef<str>) -> Result<Generator, GeneratorError> {
+        if self.queues.is_empty() {
+            return Err(GeneratorError::NoQueues);
+        }
+        if self.batch_size == 0 {
+            return Err(GeneratorError::InvalidBatchSize);
+        }
+
+        let packet_len = self.template.packet_len() as u32;
+        let worker_count = self.queues.len() as u64;
+        let clock = match self.rate {
+            Rate::Unlimited => None,
+            _ => Some(Clock::new(self.clock_source).map_err(ShaperError::from)?),
+        };
+        let mut workers = Vec::with_capacity(self.queues.len());
+        for (index, queue) in self.queues.iter().enumerate() {
+            let (mut tx_socket, _, umem) = self
+                .socket_builder
+                .clone()
+                .build(interface_name.as_ref(), queue.queue_id)?;
+            write_template(&mut tx_socket, &umem, &self.template)?;
+            let seed = self.seed.map(|seed| seed.wrapping_add(index as u64));
+            let template = match self.variations.is_empty() {
+                true => None,
+                false => Some(FlowTemplate::new(
+                    self.template.clone(),
+                    &self.variations,
+                    seed,
+                )?),
+            };
+
+            // Every worker may catch up by a batch, so that it can keep
+            // batching when it falls behind by a few packets.
+            let rate_controller = clock
+                .map(|clock| {
+                    RateControllerBuilder {
+                        rate: worker_rate(self.rate, worker_count),
+                        profile: self.profile,
+                        max_burst: self.batch_size,
+                        clock_source: clock.source(),
+                        seed,
+                    }
+                    .build_with_clock(clock)
+                })
+                .transpose()?;
+
+            workers.push(Worker {
+                queue: *queue,
+                tx_socket,
+                umem,
+                packet_len,
+                template,
+                batch_size: self.batch_size,
+                rate_controller,
+                packet_limit: packet_limit(self.limit, index as u64, worker_count),
+                duration: match self.limit {
+                    Limit::Duration(duration) => Some(duration),
+                    _ => None,
+                },
+                drain_timeout: self.drain_timeout,
+            });
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let started_at = Instant::now();
+        let mut handles = Vec::with_capacity(workers.len());
+        for worker in workers {
+            let queue = worker.queue;
+            let worker_stop = stop.clone();
+            match mangonel_thread::spawn(queue.core_id, move || worker.run(&worker_stop)) {
+                Ok(handle) => handles.push((queue, handle)),
+                Err(error) => {
+                    // Do not leave the workers that already started running.
+                    stop.store(true, Ordering::Relaxed);
+                    for (_, handle) in handles {
+                        let _ = handle.join();
+                    }
+                    return Err(error.into());
+                }
+            }
+        }
+
+        Ok(Generator {
+            handles,
+            stop,
+            started_at,
+        })
+    }
+}
+
+/// A running traffic generator with one pinned TX worker per queue.
+#[derive(Debug)]
+pub struct Generator {
+    handles: Vec<(QueueAssignment, JoinHandle<QueueReport>)>,
+    stop: Arc<AtomicBool>,
+    started_at: Instant,
+}
+
+impl Generator {
+    /// Asks every worker to stop. Use [`Generator::join`] to wait for them.
+    #[inline]
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` when every worker has reached the limit or stopped.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.handles.iter().all(|(_, handle)| handle.is_finished())
+    }
+
+    /// Waits for every worker and reports what was sent.
+    pub fn join(self) -> Result<GeneratorReport, GeneratorError> {
+        let mut queues = Vec::with_capacity(self.handles.len());
+        let mut panicked = None;
+        for (queue, handle) in self.handles {
+            match handle.join() {
+                Ok(report) => queues.push(report),
+                Err(_) => {
+                    // Keep joining so that no worker outlives the generator.
+                    self.stop.store(true, Ordering::Relaxed);
+                    panicked.get_or_insert(queue.queue_id);
+                }
+            }
+        }
+        if let Some(queue_id) = panicked {
+            return Err(GeneratorError::WorkerPanicked(queue_id));
+        }
+
+        Ok(GeneratorReport {
+            elapsed: self.started_at.elapsed(),
+            queues,
+        })
+    }
+}
+
+/// What a single worker sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueReport {
+    pub queue_id: u32,
+    /// Packets accepted by the TX ring.
+    pub packets: u64,
+    pub bytes: u64,
+    /// Packets the kernel had not completed when the drain timeout expired.
+    pub outstanding: u32,
+    pub elapsed: Duration,
+}
+
+/// What a generator sent, and the rate it achieved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorReport {
+    /// Time from the start of the first worker until every worker stopped.
+    pub elapsed: Duration,
+    pub queues: Vec<QueueReport>,
+}
+
+impl GeneratorReport {
+    #[inline]
+    pub fn packets(&self) -> u64 {
+        self.queues.iter().map(|queue| queue.packets).sum()
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> u64 {
+        self.queues.iter().map(|queue| queue.bytes).sum()
+    }
+
+    /// Achieved packet rate, measured over the time the workers were
+    /// sending.
+    pub fn packets_per_second(&self) -> f64 {
+        self.packets() as f64 / self.sending_time().as_secs_f64()
+    }
+
+    /// Achieved bit rate, counted like [`Rate::BitsPerSecond`].
+    pub fn bits_per_second(&self) -> f64 {
+        (self.bytes() * 8) as f64 / self.sending_time().as_secs_f64()
+    }
+
+    #[inline]
+    fn sending_time(&self) -> Duration {
+        self.queues
+            .iter()
+            .map(|queue| queue.elapsed)
+            .max()
+            .unwrap_or_default()
+            .max(Duration::from_nanos(1))
+    }
+}
+
+struct Worker {
+    queue: QueueAssignment,
+    tx_socket: TxSocket,
+    // The frames of `tx_socket` live in this UMEM.
+    umem: Umem,
+    packet_len: u32,
+    template: Option<FlowTemplate>,
+    batch_size: u32,
+    rate_controller: Option<RateController>,
+    packet_limit: Option<u64>,
+    duration: Option<Duration>,
+    drain_timeout: Duration,
+}
+
+impl Worker {
+    /// Longest time a worker waits for the rate controller before it checks
+    /// the stop flag again.
+    const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn run(mut self, stop: &AtomicBool) -> QueueReport {
+        let mut batch = vec![Descriptor::default(); self.batch_size as usize];
+        let started_at = Instant::now();
+        let mut sent: u64 = 0;
+
+        while !stop.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if self
+                .duration
+                .is_some_and(|duration| now - started_at >= duration)
+            {
+                break;
+            }
+
+            let mut size = self.batch_size as u64;
+            if let Some(limit) = self.packet_limit {
+                if sent >= limit {
+                    break;
+                }
+                size = size.min(limit - sent);
+            }
+            if let Some(rate_controller) = &mut self.rate_controller {
+                size = rate_controller.grant(self.packet_len, size as u32) as u64;
+                if size == 0 {
+                    rate_controller.wait(Self::STOP_CHECK_INTERVAL);
+                    continue;
+                }
+            }
+
+            let allocated = self.tx_socket.allocate(&mut batch[..size as usize]) as usize;
+            let headroom = self.umem.config().frame_headroom as usize;
+            for descriptor in &mut batch[..allocated] {
+                descriptor.length = self.packet_len;
+                if let Some(template) = &mut self.template {
+                    let packet = &mut descriptor.as_slice_mut(&self.umem)[headroom..];
+                    descriptor.length = template.apply(packet) as u32;
+                }
+            }
+
+            let outcome = match allocated {
+                // Every frame is in flight.
+                0 => self.tx_socket.reclaim(),
+                _ => self.tx_socket.write(&batch[..allocated]),
+            };
+            let accepted = outcome.accepted as usize;
+            self.tx_socket.free(&batch[accepted..allocated]);
+            if let Some(rate_controller) = &mut self.rate_controller {
+                rate_controller.refund(self.packet_len, (size as usize - accepted) as u32);
+            }
+            sent += accepted as u64;
+        }
+        let elapsed = started_at.elapsed();
+
+        let drain_deadline = Instant::now() + self.drain_timeout;
+        while self.tx_socket.outstanding() > 0 && Instant::now() < drain_deadline {
+            self.tx_socket.reclaim();
+            std::hint::spin_loop();
+        }
+
+        let counters = self.tx_socket.counters();
+        QueueReport {
+            queue_id: self.queue.queue_id,
+            packets: counters.packets,
+            bytes: counters.bytes,
+            outstanding: self.tx_socket.outstanding(),
+            elapsed,
+        }
+    }
+}
+
+/// Writes the template into every free TX frame once, so that the workers
+/// only have to submit descriptors. Transmission does not modify the
+/// frames, so they still hold the packet when they come back.
+fn write_template(
+    tx_socket: &mut TxSocket,
+    umem: &Umem,
+    template: &PacketBuilder,
+) -> Result<(), GeneratorError> {
+    let mut batch = vec![Descriptor::default(); tx_socket.frame_allocator().frame_count() as usize];
+    let allocated = tx_socket.allocate(&mut batch) as usize;
+    let headroom = umem.config().frame_headroom as usize;
+    let result = batch[..allocated].iter_mut().try_for_each(|descriptor| {
+        template.write(&mut descriptor.as_slice_mut(umem)[headroom..])?;
+        Ok(())
+    });
+    tx_socket.free(&batch[..allocated]);
+    result
+}
+
+/// The share of the rate that goes to each of `worker_count` workers.
+fn worker_rate(rate: Rate, worker_count: u64) -> Rate {
+    match rate {
+        Rate::Unlimited => Rate::Unlimited,
+        Rate::PacketsPerSecond(rate) => Rate::PacketsPerSecond((rate / worker_count).max(1)),
+        Rate::BitsPerSecond(rate) => Rate::BitsPerSecond((rate / worker_count).max(1)),
+    }
+}
+
+/// The share of a packet limit that goes to worker `index`. The remainder
+/// goes to the first workers.
+fn packet_limit(limit: Limit, index: u64, worker_count: u64) -> Option<u64> {
+    match limit {
+        Limit::Packets(packets) => {
+            let share = packets / worker_count;
+            Some(share + (index < packets % worker_count) as u64)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GeneratorError {
+    #[error("No queues to send on")]
+    NoQueues,
+    #[error("Batch size must be at least 1")]
+    InvalidBatchSize,
+    #[error("Failed to build socket: {0}")]
+    Socket(#[from] SocketError),
+    #[error("Failed to write template packet: {0}")]
+    Template(#[from] PacketError),
+    #[error("Invalid field variation: {0}")]
+    Variation(#[from] TemplateError),
+    #[error("Failed to set up rate control: {0}")]
+    Shaper(#[from] ShaperError),
+    #[error("Failed to spawn worker: {0}")]
+    Thread(#[from] ThreadError),
+    #[error("Worker for queue {0} panicked")]
+    WorkerPanicked(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keeps_ingress_traffic() {
+        assert!(
+            GeneratorBuilder::default()
+                .socket_builder
+                .inhibit_program_load
+        );
+    }
+
+    #[test]
+    fn split_rate_and_limit() {
+        assert_eq!(worker_rate(Rate::Unlimited, 2), Rate::Unlimited);
+        assert_eq!(
+            worker_rate(Rate::PacketsPerSecond(1_000_000), 4),
+            Rate::PacketsPerSecond(250_000)
+        );
+        assert_eq!(
+            worker_rate(Rate::BitsPerSecond(1_000_000_000), 2),
+            Rate::BitsPerSecond(500_000_000)
+        );
+
+        let limits: Vec<_> = (0..3)
+            .map(|index| packet_limit(Limit::Packets(10), index, 3).unwrap())
+            .collect();
+        assert_eq!(limits, [4, 3, 3]);
+        assert_eq!(
+            packet_limit(Limit::Duration(Duration::from_secs(1)), 0, 3),
+            None
+        );
+    }
+
+    #[test]
+    fn report_rates() {
+        let report = GeneratorReport {
+            elapsed: Duration::from_secs(3),
+            queues: vec![
+                QueueReport {
+                    queue_id: 0,
+                    packets: 1000,
+                    bytes: 64_000,
+                    outstanding: 0,
+                    elapsed: Duration::from_secs(2),
+                },
+                QueueReport {
+                    queue_id: 1,
+                    packets: 1000,
+                    bytes: 64_000,
+                    outstanding: 0,
+                    elapsed: Duration::from_secs(1),
+                },
+            ],
+        };
+        assert_eq!(report.packets(), 2000);
+        assert_eq!(report.packets_per_second(), 1000.0);
+        assert_eq!(report.bits_per_second(), 512_000.0);
+    }
+}
@@ -1 +1,17 @@
+//! A packet launcher built on AF_XDP.
 
+mod clock;
+mod generator;
+mod random;
+mod runtime;
+mod shaper;
+mod template;
+
+pub use clock::{Clock, ClockError, ClockSource};
+pub use generator::{
+    Generator, GeneratorBuilder, GeneratorError, GeneratorReport, Limit, QueueAssignment,
+    QueueReport,
+};
+pub use runtime::{QueueContext, Runtime, RuntimeBuilder, RuntimeError};
+pub use shaper::{BurstProfile, Rate, RateController, RateControllerBuilder, ShaperError};
+pub use template::{Field, FieldVariation, FlowTemplate, TemplateError, Variation};
@@ -0,0 +1,131 @@
+/// xoshiro256** pseudo-random number generator.
+///
+/// It is fast enough to draw a value per packet. It is not cryptographically
+/// secure, which traffic generation does not need.
+#[derive(Clone, Debug)]
+pub(crate) struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// Seeds the generator from the operating system.
+    pub(crate) fn from_entropy() -> Result<Self, getrandom::Error> {
+        Ok(Self::from_seed(getrandom::u64()?))
+    }
+
+    /// Seeds the generator deterministically. The same seed always yields
+    /// the same sequence.
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        // Expand the seed with SplitMix64 so that similar seeds give
+        // unrelated states and the state is never all zeros.
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut value = seed;
+            value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            value ^ (value >> 31)
+        };
+        Self {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    #[inline]
+    pub(crate) fn next_u128(&mut self) -> u128 {
+        ((self.next_u64() as u128) << 64) | self.next_u64() as u128
+    }
+
+    /// A uniform value in `0..bound`, which must not be zero.
+    ///
+    /// Bounds that fit into 64 bits use Lemire's multiply-shift, larger
+    /// ones rejection sampling. Either way, no value is more likely than
+    /// another, unlike with a plain modulo.
+    #[inline]
+    pub(crate) fn next_below(&mut self, bound: u128) -> u128 {
+        if let Ok(bound) = u64::try_from(bound) {
+            return self.next_below_u64(bound) as u128;
+        }
+
+        // 2^128 is `threshold` more than a multiple of `bound`, so values
+        // below it would make the smallest results more likely.
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = self.next_u128();
+            if value >= threshold {
+                return value % bound;
+            }
+        }
+    }
+
+    #[inline]
+    fn next_below_u64(&mut self, bound: u64) -> u64 {
+        let mut product = self.next_u64() as u128 * bound as u128;
+        // The division only runs when a retry is possible at all.
+        if (product as u64) < bound {
+            let threshold = bound.wrapping_neg() % bound;
+            while (product as u64) < threshold {
+                product = self.next_u64() as u128 * bound as u128;
+            }
+        }
+        (product >> 64) as u64
+    }
+
+    /// A uniform value in `[0, 1)`.
+    #[inline]
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_seed() {
+        let mut a = Rng::from_seed(42);
+        let mut b = Rng::from_seed(42);
+        let mut c = Rng::from_seed(43);
+        for _ in 0..16 {
+            let value = a.next_u64();
+            assert_eq!(value, b.next_u64());
+            assert_ne!(value, c.next_u64());
+        }
+
+        let value = a.next_f64();
+        assert!((0.0..1.0).contains(&value));
+        assert!(Rng::from_entropy().is_ok());
+    }
+
+    #[test]
+    fn below() {
+        let mut rng = Rng::from_seed(7);
+        let mut counts = [0u32; 3];
+        for _ in 0..30_000 {
+            counts[rng.next_below(3) as usize] += 1;
+        }
+        for count in counts {
+            assert!((9_000..11_000).contains(&count), "{counts:?}");
+        }
+
+        assert_eq!(rng.next_below(1), 0);
+        let bound = (1 << 100) + 3;
+        assert!((0..64).all(|_| rng.next_below(bound) < bound));
+    }
+}
@@ -0,0 +1,203 @@
+use mangonel_libxdp::{RxSocket, SocketBuilder, SocketError, TxSocket, Umem};
+use mangonel_nic::NetworkInterface;
+use mangonel_thread::{
+    CancellationToken, GroupError, ThreadError, Topology, WorkerGroup, interface_numa_node,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Binds a socket to every queue of an interface and runs a worker per
+/// queue, each pinned to its own core.
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeBuilder {
+    /// Used to build one socket per queue. The UMEMs are bound to the NUMA
+    /// node of the interface unless `numa_node` is set.
+    pub socket_builder: SocketBuilder,
+    /// Queues to bind. When `None`, every queue that a socket can be bound
+    /// to, i.e. the combined channels followed by the larger of the RX-only
+    /// and TX-only channel counts.
+    pub queues: Option<Vec<u32>>,
+    /// Cores to pin the workers to, in the order of `queues`. When `None`,
+    /// cores on the NUMA node of the interface are used, or every core
+    /// when the node is unknown.
+    pub cores: Option<Vec<usize>>,
+}
+
+impl RuntimeBuilder {
+    /// Builds a socket per queue and runs `worker` on each of them.
+    ///
+    /// Every socket is built before any worker starts, so a queue that
+    /// cannot be bound fails without running anything. `worker` should
+    /// return once [`QueueContext::is_stopped`] returns `true`, and an
+    /// error stops the other workers.
+    pub fn spawn<F, E>(
+        self,
+        interface: &NetworkInterface,
+        worker: F,
+    ) -> Result<Runtime<E>, RuntimeError>
+    where
+        F: Fn(QueueContext) -> Result<(), E> + Send + Sync + 'static,
+        E: Send + 'static,
+    {
+        let queues = match self.queues {
+            Some(queues) => queues,
+            None => (0..interface.get_queue_count()?).collect(),
+        };
+        if queues.is_empty() {
+            return Err(RuntimeError::NoQueues);
+        }
+        let numa_node = interface_numa_node(interface.name());
+        let cores = match self.cores {
+            Some(cores) => cores,
+            None => local_cores(numa_node)?,
+        };
+        if cores.len() < queues.len() {
+            return Err(RuntimeError::NotEnoughCores {
+                queues: queues.len(),
+                cores: cores.len(),
+            });
+        }
+
+        let mut socket_builder = self.socket_builder;
+        socket_builder.numa_node = socket_builder.numa_node.or(numa_node);
+
+        let mut group = WorkerGroup::new();
+        let mut contexts = Vec::with_capacity(queues.len());
+        for (&queue_id, &core_id) in queues.iter().zip(&cores) {
+            let (tx_socket, rx_socket, umem) = socket_builder
+                .clone()
+                .build(interface, queue_id)
+                .map_err(|source| RuntimeError::Socket { queue_id, source })?;
+            contexts.push(QueueContext {
+                queue_id,
+                core_id,
+                tx_socket,
+                rx_socket,
+                umem,
+                token: group.token(),
+            });
+        }
+
+        // On error, dropping the group stops the workers that already run.
+        let worker = Arc::new(worker);
+        for context in contexts {
+            let worker = worker.clone();
+            group.spawn(context.core_id, move |_| worker(context))?;
+        }
+
+        Ok(Runtime { group })
+    }
+}
+
+/// What a worker of a [`Runtime`] owns: the sockets bound to its queue and
+/// the UMEM behind them.
+pub struct QueueContext {
+    pub queue_id: u32,
+    pub core_id: usize,
+    pub tx_socket: TxSocket,
+    pub rx_socket: RxSocket,
+    pub umem: Umem,
+    token: CancellationToken,
+}
+
+impl QueueContext {
+    /// Returns `true` once the runtime was stopped or any worker failed.
+    #[inline]
+    pub fn is_stopped(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// The token shared by every worker of the runtime, e.g. to sleep
+    /// until it is stopped.
+    #[inline]
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+/// Pinned workers running on the queues of an interface.
+///
+/// A worker that fails or panics stops the others. Dropping the runtime
+/// stops the workers and waits for them, which closes their sockets.
+#[derive(Debug)]
+pub struct Runtime<E> {
+    group: WorkerGroup<E>,
+}
+
+impl<E: Send + 'static> Runtime<E> {
+    /// Asks every worker to stop. Use [`Runtime::join`] to wait for them.
+    #[inline]
+    pub fn stop(&self) {
+        self.group.cancel();
+    }
+
+    #[inline]
+    pub fn token(&self) -> CancellationToken {
+        self.group.token()
+    }
+
+    /// Stops the workers on SIGINT or SIGTERM.
+    #[inline]
+    pub fn stop_on_signal(&self) -> Result<(), RuntimeError> {
+        Ok(self.group.cancel_on_signal()?)
+    }
+
+    /// Returns `true` when every worker has returned.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.group.is_finished()
+    }
+
+    /// Waits for every worker and returns the first failure, if any.
+    #[inline]
+    pub fn join(self) -> Result<(), GroupError<E>> {
+        self.group.join()
+    }
+
+    /// Like [`Runtime::join`], but gives up after `timeout`.
+    #[inline]
+    pub fn join_timeout(self, timeout: Duration) -> Result<(), GroupError<E>> {
+        self.group.join_timeout(timeout)
+    }
+}
+
+/// Cores on the NUMA node of the interface that this process may run on.
+/// Falls back to every available core when the node is unknown or has none
+/// of them.
+fn local_cores(numa_node: Option<u32>) -> Result<Vec<usize>, RuntimeError> {
+    let local = match numa_node {
+        Some(node) => Topology::discover()?
+            .available_cores(node)
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    match local.is_empty() {
+        true => Ok(mangonel_thread::core_ids()?),
+        false => Ok(local),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuntimeError {
+    #[error("No queues to bind")]
+    NoQueues,
+    #[error("{queues} queues need as many cores, but only {cores} are available")]
+    NotEnoughCores { queues: usize, cores: usize },
+    #[error("Failed to get the queue count: {0}")]
+    QueueCount(#[from] mangonel_nic::Error),
+    #[error("Failed to build socket on queue {queue_id}: {source}")]
+    Socket { queue_id: u32, source: SocketError },
+    #[error("Failed to spawn worker: {0}")]
+    Thread(#[from] ThreadError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_cores_fall_back() {
+        // Without a NUMA node, every core is used.
+        let cores = local_cores(None).unwrap();
+        assert_eq!(cores, mangonel_thread::core_ids().unwrap());
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{
+    clock::{Clock, ClockError, ClockSource},
+    random::Rng,
+};
+use std::time::Duration;
+
+/// Target sending rate. Bit rates count the bytes of the frame as written,
+/// without preamble, FCS and inter-frame gap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rate {
+    /// Send as fast as the TX rings accept packets.
+    #[default]
+    Unlimited,
+    PacketsPerSecond(u64),
+    BitsPerSecond(u64),
+}
+
+/// How departures are spread over time. Every profile averages out to the
+/// target [`Rate`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BurstProfile {
+    /// Evenly spaced packets.
+    #[default]
+    Constant,
+    /// Exponentially distributed gaps, i.e. a Poisson arrival process.
+    Poisson,
+    /// Evenly spaced packets during `on`, then silence during `off`. The
+    /// rate during `on` is raised so that the average matches the target.
+    OnOff { on: Duration, off: Duration },
+}
+
+#[derive(Clone, Debug)]
+pub struct RateControllerBuilder {
+    pub rate: Rate,
+    pub profile: BurstProfile,
+    /// Packets that may go out back to back to catch up after the sender
+    /// fell behind, e.g. while the TX ring was full. `1` keeps the spacing
+    /// exact; larger values allow batching at the cost of short bursts.
+    pub max_burst: u32,
+    pub clock_source: ClockSource,
+    /// Seed for [`BurstProfile::Poisson`]. Seeded from the operating system
+    /// when `None`.
+    pub seed: Option<u64>,
+}
+
+impl Default for RateControllerBuilder {
+    fn default() -> Self {
+        Self {
+            rate: Rate::Unlimited,
+            profile: BurstProfile::Constant,
+            max_burst: 1,
+            clock_source: ClockSource::Auto,
+            seed: None,
+        }
+    }
+}
+
+impl RateControllerBuilder {
+    pub fn build(self) -> Result<RateController, ShaperError> {
+        let clock = Clock::new(self.clock_source)?;
+        self.build_with_clock(clock)
+    }
+
+    /// Builds on an existing clock, which skips the TSC calibration. The
+    /// `clock_source` field is ignored.
+    pub fn build_with_clock(self, clock: Clock) -> Result<RateController, ShaperError> {
+        if matches!(
+            self.rate,
+            Rate::PacketsPerSecond(0) | Rate::BitsPerSecond(0)
+        ) {
+            return Err(ShaperError::ZeroRate);
+        }
+        if self.max_burst == 0 {
+            return Err(ShaperError::ZeroBurst);
+        }
+
+        // Fraction of the time spent sending, and the length of a cycle.
+        let (duty_cycle, period) = match self.profile {
+            BurstProfile::OnOff { on, off } => {
+                if on.is_zero() {
+                    return Err(ShaperError::ZeroOnTime);
+                }
+                let period = on + off;
+                (
+                    on.as_nanos() as f64 / period.as_nanos() as f64,
+                    period.as_nanos() as f64,
+                )
+            }
+            _ => (1.0, 0.0),
+        };
+
+        let rng = match self.seed {
+            Some(seed) => Rng::from_seed(seed),
+            None => Rng::from_entropy().map_err(ShaperError::Seed)?,
+        };
+
+        Ok(RateController {
+            clock,
+            rate: self.rate,
+            profile: self.profile,
+            max_burst: self.max_burst,
+            duty_cycle,
+            period,
+            next_departure: None,
+            rng,
+        })
+    }
+}
+
+/// Paces packets for a [`TxSocket`].
+///
+/// The controller keeps a schedule of departure times and grants packets
+/// whose departure is due. It works like a token bucket whose depth is
+/// `max_burst` packets, so a sender that stalls can only catch up by that
+/// many packets. Use it like this:
+///
+/// 1. [`RateController::grant`] returns how many packets may be sent now.
+/// 2. Submit them with [`TxSocket::write`].
+/// 3. Give back the ones the ring did not accept with
+///    [`RateController::refund`].
+/// 4. When nothing was granted, call [`RateController::wait`].
+///
+/// [`TxSocket`]: mangonel_libxdp::TxSocket
+/// [`TxSocket::write`]: mangonel_libxdp::TxSocket::write
+#[derive(Debug)]
+pub struct RateController {
+    clock: Clock,
+    rate: Rate,
+    profile: BurstProfile,
+    max_burst: u32,
+    duty_cycle: f64,
+    /// Length of an on/off cycle in nanoseconds.
+    period: f64,
+    /// Departure time of the next packet in clock nanoseconds. Set on the
+    /// first grant so that the time between building and sending does not
+    /// count as falling behind.
+    next_departure: Option<f64>,
+    rng: Rng,
+}
+
+impl RateController {
+    /// Grants up to `max` packets of `packet_len` bytes that are due now.
+    /// The granted packets are taken off the schedule.
+    pub fn grant(&mut self, packet_len: u32, max: u32) -> u32 {
+        if self.rate == Rate::Unlimited {
+            return max;
+        }
+
+        let now = self.clock.now() as f64;
+        let gap = self.gap(packet_len);
+        let earliest = now - (self.max_burst - 1) as f64 * gap;
+        let mut next_departure = self.next_departure.unwrap_or(now).max(earliest);
+        if self.period > 0.0 {
+            next_departure = self.skip_off_time(next_departure);
+        }
+
+        let mut granted = 0;
+        while granted < max && next_departure <= now {
+            next_departure = self.advance(next_departure, gap);
+            granted += 1;
+        }
+        self.next_departure = Some(next_departure);
+        granted
+    }
+
+    /// Puts `count` granted packets that were not sent back on the schedule.
+    pub fn refund(&mut self, packet_len: u32, count: u32) {
+        if self.rate == Rate::Unlimited || count == 0 {
+            return;
+        }
+        let gap = self.gap(packet_len);
+        if let Some(next_departure) = &mut self.next_departure {
+            *next_departure -= count as f64 * gap;
+        }
+    }
+
+    /// Waits until the next packet is due, but at most `timeout` so that the
+    /// caller can check for a stop request.
+    pub fn wait(&self, timeout: Duration) {
+        let now = self.clock.now();
+        let deadline = now + timeout.as_nanos() as u64;
+        let next_departure = self.next_departure.map_or(now, |next| next as u64);
+        self.clock.wait_until(next_departure.min(deadline));
+    }
+
+    /// Departure time of the next packet on the clock, in nanoseconds.
+    #[inline]
+    pub fn next_departure(&self) -> Option<u64> {
+        self.next_departure.map(|next| next as u64)
+    }
+
+    #[inline]
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    #[inline]
+    pub fn rate(&self) -> Rate {
+        self.rate
+    }
+
+    /// Gap between two packets while sending, in nanoseconds.
+    #[inline]
+    fn gap(&self, packet_len: u32) -> f64 {
+        let gap = match self.rate {
+            Rate::Unlimited => 0.0,
+            Rate::PacketsPerSecond(rate) => 1e9 / rate as f64,
+            Rate::BitsPerSecond(rate) => packet_len as f64 * 8.0 * 1e9 / rate as f64,
+        };
+        gap * self.duty_cycle
+    }
+
+    #[inline]
+    fn advance(&mut self, departure: f64, gap: f64) -> f64 {
+        match self.profile {
+            BurstProfile::Constant => departure + gap,
+            // Inverse transform sampling of the exponential distribution.
+            BurstProfile::Poisson => departure - (1.0 - self.rng.next_f64()).ln() * gap,
+            BurstProfile::OnOff { .. } => self.skip_off_time(departure + gap),
+        }
+    }
+
+    /// Moves a departure that falls into an off period to the start of the
+    /// next on period.
+    #[inline]
+    fn skip_off_time(&self, departure: f64) -> f64 {
+        let on_time = self.period * self.duty_cycle;
+        let position = departure.rem_euclid(self.period);
+        if position < on_time {
+            departure
+        } else {
+            departure - position + self.period
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShaperError {
+    #[error("Rate must be greater than 0")]
+    ZeroRate,
+    #[error("Maximum burst must be at least 1 packet")]
+    ZeroBurst,
+    #[error("On time of an on/off profile must be greater than 0")]
+    ZeroOnTime,
+    #[error("Failed to seed the random number generator: {0}")]
+    Seed(getrandom::Error),
+    #[error(transparent)]
+    Clock(#[from] ClockError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(rate: Rate, profile: BurstProfile, max_burst: u32) -> RateController {
+        RateControllerBuilder {
+            rate,
+            profile,
+            max_burst,
+            seed: Some(7),
+            ..Default::default()
+        }
+        .build_with_clock(Clock::new(ClockSource::MonotonicRaw).unwrap())
+        .unwrap()
+    }
+
+    #[test]
+    fn constant_spacing() {
+        let mut controller = controller(Rate::PacketsPerSecond(1_000_000), Default::default(), 1);
+        assert_eq!(controller.gap(64), 1000.0);
+        assert_eq!(controller.advance(5000.0, 1000.0), 6000.0);
+
+        // 1000 byte packets at 8 Mbit/s are 1000 packets per second.
+        let controller = self::controller(Rate::BitsPerSecond(8_000_000), Default::default(), 1);
+        assert_eq!(controller.gap(1000), 1e6);
+    }
+
+    #[test]
+    fn burst_is_capped() {
+        let mut controller = controller(Rate::PacketsPerSecond(1_000_000), Default::default(), 4);
+        controller.grant(64, 1);
+
+        // Fall far behind, then catch up by at most `max_burst` packets.
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(controller.grant(64, 1000), 4);
+
+        controller.refund(64, 3);
+        assert!(controller.grant(64, 1000) >= 3);
+    }
+
+    #[test]
+    fn poisson_mean() {
+        let mut controller = controller(Rate::PacketsPerSecond(1_000), BurstProfile::Poisson, 1);
+        let mut departure = 0.0;
+        for _ in 0..100_000 {
+            departure = controller.advance(departure, 1000.0);
+        }
+        let mean = departure / 100_000.0;
+        assert!((mean - 1000.0).abs() < 20.0, "{mean}");
+    }
+
+    #[test]
+    fn on_off_cycles() {
+        let profile = BurstProfile::OnOff {
+            on: Duration::from_micros(10),
+            off: Duration::from_micros(30),
+        };
+        let mut controller = controller(Rate::PacketsPerSecond(1_000_000), profile, 1);
+        // Four times the rate while on, so the average stays 1 Mpps.
+        let gap = controller.gap(64);
+        assert_eq!(gap, 250.0);
+
+        let mut departure = 0.0;
+        let mut departures = Vec::new();
+        while departure < 80_000.0 {
+            departures.push(departure);
+            departure = controller.advance(departure, gap);
+        }
+        assert_eq!(departures.len(), 80);
+        assert!(
+            departures
+                .iter()
+                .all(|departure| departure.rem_euclid(40_000.0) < 10_000.0)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let builder = RateControllerBuilder {
+            rate: Rate::BitsPerSecond(0),
+            seed: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(builder.build(), Err(ShaperError::ZeroRate)));
+    }
+}
@@ -0,0 +1,655 @@
+use crate::random::Rng;
+use mangonel_packet::{
+    EthernetFrame, NetworkHeader, PacketBuilder, PacketError, PacketLayout, TransportHeader,
+    VlanTag, checksum, read_u16, write_u16,
+};
+
+/// A header field that a [`FlowTemplate`] can vary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    SourceMac,
+    DestinationMac,
+    /// VLAN ID of the tag at this index, counted from the outermost tag.
+    VlanId(usize),
+    /// IPv4 or IPv6 source address, as an integer in network order.
+    SourceIp,
+    DestinationIp,
+    SourcePort,
+    DestinationPort,
+    /// Number of payload bytes, up to the payload length of the template.
+    /// The payload is truncated, and the length fields are adjusted.
+    PayloadLength,
+}
+
+/// How the value of a field changes from packet to packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variation {
+    /// A uniformly distributed value in `min..=max`.
+    Random { min: u128, max: u128 },
+    /// `start`, `start + step`, `start + 2 * step`, ... wrapping around at
+    /// the largest value of the field.
+    Sequence { start: u128, step: u128 },
+    /// `min`, `min + step`, ... up to `max`, then `min` again.
+    Sweep { min: u128, max: u128, step: u128 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldVariation {
+    pub field: Field,
+    pub variation: Variation,
+}
+
+/// A packet whose fields change from one packet to the next.
+///
+/// The base packet is written into a frame once with
+/// [`FlowTemplate::write`]. After that, [`FlowTemplate::apply`] only
+/// rewrites the varying fields and patches the checksums incrementally
+/// (RFC 1624). The old field values are read from the frame itself, so a
+/// frame can be patched no matter which values it was last sent with.
+#[derive(Debug)]
+pub struct FlowTemplate {
+    packet: PacketBuilder,
+    layout: PacketLayout,
+    fields: Vec<FieldState>,
+    /// Folded sum of the first `n` payload bytes at index `n`.
+    payload_sums: Vec<u16>,
+    rng: Rng,
+}
+
+#[derive(Debug)]
+struct FieldState {
+    field: Field,
+    variation: Variation,
+    /// Largest value the field can hold.
+    max: u128,
+    /// Next value of a sequence or sweep.
+    next: u128,
+}
+
+impl FlowTemplate {
+    /// `seed` makes random values reproducible. The generator is seeded
+    /// from the operating system when it is `None`.
+    pub fn new(
+        packet: PacketBuilder,
+        variations: &[FieldVariation],
+        seed: Option<u64>,
+    ) -> Result<Self, TemplateError> {
+        let fields = variations
+            .iter()
+            .map(|variation| FieldState::new(variation, &packet))
+            .collect::<Result<_, _>>()?;
+
+        let mut payload_sums = Vec::with_capacity(packet.payload.len() + 1);
+        payload_sums.push(0);
+        for length in 1..=packet.payload.len() {
+            let accumulator = checksum::sum(0, &packet.payload[..length]);
+            payload_sums.push(!checksum::fold(accumulator));
+        }
+
+        let rng = match seed {
+            Some(seed) => Rng::from_seed(seed),
+            None => Rng::from_entropy().map_err(TemplateError::Seed)?,
+        };
+
+        Ok(Self {
+            layout: packet.layout(),
+            packet,
+            fields,
+            payload_sums,
+            rng,
+        })
+    }
+
+    #[inline]
+    pub fn packet(&self) -> &PacketBuilder {
+        &self.packet
+    }
+
+    /// Length of the base packet, which is also the largest packet.
+    #[inline]
+    pub fn packet_len(&self) -> usize {
+        self.layout.packet_len
+    }
+
+    /// Writes the base packet without any variation applied.
+    #[inline]
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, PacketError> {
+        self.packet.write(buffer)
+    }
+
+    /// Applies the next value of every field to a packet written by
+    /// [`FlowTemplate::write`] and returns the packet length.
+    ///
+    /// # Panics
+    ///
+    /// The function panics when `packet` is shorter than
+    /// [`FlowTemplate::packet_len`].
+    pub fn apply(&mut self, packet: &mut [u8]) -> usize {
+        assert!(
+            packet.len() >= self.layout.packet_len,
+            "Packet buffer of {} bytes is shorter than the template",
+            packet.len()
+        );
+
+        let mut length = self.layout.packet_len;
+        for index in 0..self.fields.len() {
+            let value = self.fields[index].next_value(&mut self.rng);
+            match self.fields[index].field {
+                Field::SourceMac => packet[6..12].copy_from_slice(&value.to_be_bytes()[10..]),
+                Field::DestinationMac => packet[0..6].copy_from_slice(&value.to_be_bytes()[10..]),
+                Field::VlanId(tag) => {
+                    let offset = EthernetFrame::HEADER_LEN + tag * VlanTag::LEN;
+                    let tci = (read_u16(packet, offset) & 0xf000) | value as u16;
+                    write_u16(packet, offset, tci);
+                }
+                Field::SourceIp => self.set_address(packet, 12, 8, value),
+                Field::DestinationIp => self.set_address(packet, 16, 24, value),
+                Field::SourcePort => self.set_port(packet, 0, value as u16),
+                Field::DestinationPort => self.set_port(packet, 2, value as u16),
+                Field::PayloadLength => length = self.set_payload_len(packet, value as usize),
+            }
+        }
+        length
+    }
+
+    /// `ipv4_offset` and `ipv6_offset` are the offsets of the address in the
+    /// respective header.
+    fn set_address(&self, packet: &mut [u8], ipv4_offset: usize, ipv6_offset: usize, value: u128) {
+        let network_offset = self.layout.network_offset.unwrap();
+        match self.packet.network {
+            Some(NetworkHeader::Ipv4(_)) => {
+                let new = (value as u32).to_be_bytes();
+                self.patch(packet, network_offset + ipv4_offset, &new, true);
+            }
+            _ => {
+                let new = value.to_be_bytes();
+                self.patch(packet, network_offset + ipv6_offset, &new, true);
+            }
+        }
+    }
+
+    fn set_port(&self, packet: &mut [u8], offset: usize, value: u16) {
+        let transport_offset = self.layout.transport_offset.unwrap();
+        self.patch(
+            packet,
+            transport_offset + offset,
+            &value.to_be_bytes(),
+            false,
+        );
+    }
+
+    fn set_payload_len(&self, packet: &mut [u8], payload_len: usize) -> usize {
+        let Some(network_offset) = self.layout.network_offset else {
+            return self.layout.payload_offset + payload_len;
+        };
+        let transport_len = self.layout.payload_offset
+            - self
+                .layout
+                .transport_offset
+                .unwrap_or(self.layout.payload_offset);
+        let upper_layer_len = (transport_len + payload_len) as u16;
+
+        // Every length field counts the transport header and the payload,
+        // plus the header itself for the IPv4 total length.
+        let old_upper_layer_len = match self.packet.network {
+            Some(NetworkHeader::Ipv4(_)) => {
+                let offset = network_offset + 2;
+                let header_len =
+                    (self.layout.payload_offset - network_offset) as u16 - transport_len as u16;
+                let old = read_u16(packet, offset);
+                self.patch(
+                    packet,
+                    offset,
+                    &(header_len + upper_layer_len).to_be_bytes(),
+                    false,
+                );
+                old - header_len
+            }
+            _ => {
+                let offset = network_offset + 4;
+                let old = read_u16(packet, offset);
+                write_u16(packet, offset, upper_layer_len);
+                old
+            }
+        };
+        let old_payload_len =
+            (old_upper_layer_len as usize - transport_len).min(self.packet.payload.len());
+
+        if let Some((checksum_offset, is_udp)) = self.transport_checksum() {
+            let transport_offset = self.layout.transport_offset.unwrap();
+            let mut checksum = read_u16(packet, checksum_offset);
+            let old = old_upper_layer_len.to_be_bytes();
+            let new = upper_layer_len.to_be_bytes();
+            if self.has_pseudo_header() {
+                checksum = checksum::update(checksum, &old, &new);
+            }
+            if is_udp {
+                checksum = checksum::update(checksum, &old, &new);
+                write_u16(packet, transport_offset + 4, upper_layer_len);
+            }
+            // Truncating the payload has the same effect on the sum as
+            // zeroing the bytes past the new end.
+            checksum = checksum::adjust(
+                checksum,
+                self.payload_sums[old_payload_len],
+                self.payload_sums[payload_len],
+            );
+            write_u16(packet, checksum_offset, fix_udp_checksum(checksum, is_udp));
+        }
+
+        self.layout.payload_offset + payload_len
+    }
+
+    /// Overwrites the field at `offset` with `new` and patches the checksums
+    /// that cover it. `in_pseudo_header` marks IP addresses, which the
+    /// transport checksum covers through the pseudo-header.
+    fn patch(&self, packet: &mut [u8], offset: usize, new: &[u8], in_pseudo_header: bool) {
+        let old = &packet[offset..offset + new.len()];
+        let transport_offset = self.layout.transport_offset.unwrap_or(usize::MAX);
+        let network_offset = self.layout.network_offset.unwrap_or(usize::MAX);
+
+        let ipv4_checksum = match self.packet.network {
+            Some(NetworkHeader::Ipv4(_)) if offset < transport_offset => {
+                let checksum_offset = network_offset + 10;
+                Some((
+                    checksum_offset,
+                    checksum::update(read_u16(packet, checksum_offset), old, new),
+                ))
+            }
+            _ => None,
+        };
+        let transport_checksum = self
+            .transport_checksum()
+            .filter(|_| offset >= transport_offset || in_pseudo_header && self.has_pseudo_header())
+            .map(|(checksum_offset, is_udp)| {
+                let checksum = checksum::update(read_u16(packet, checksum_offset), old, new);
+                (checksum_offset, fix_udp_checksum(checksum, is_udp))
+            });
+
+        packet[offset..offset + new.len()].copy_from_slice(new);
+        for (checksum_offset, checksum) in ipv4_checksum.into_iter().chain(transport_checksum) {
+            write_u16(packet, checksum_offset, checksum);
+        }
+    }
+
+    /// Offset of the transport checksum, and whether it is a UDP checksum.
+    #[inline]
+    fn transport_checksum(&self) -> Option<(usize, bool)> {
+        let transport_offset = self.layout.transport_offset?;
+        match self.packet.transport.as_ref()? {
+            TransportHeader::Udp(_) => Some((transport_offset + 6, true)),
+            TransportHeader::Tcp(_) => Some((transport_offset + 16, false)),
+            TransportHeader::Icmp(_) => Some((transport_offset + 2, false)),
+        }
+    }
+
+    /// Whether the transport checksum covers a pseudo-header. ICMPv4 is the
+    /// only transport without one.
+    #[inline]
+    fn has_pseudo_header(&self) -> bool {
+        !matches!(
+            (&self.packet.network, &self.packet.transport),
+            (Some(NetworkHeader::Ipv4(_)), Some(TransportHeader::Icmp(_)))
+        )
+    }
+}
+
+impl FieldState {
+    fn new(variation: &FieldVariation, packet: &PacketBuilder) -> Result<Self, TemplateError> {
+        let field = variation.field;
+        let max = field_max(field, packet).ok_or(TemplateError::MissingHeader(field))?;
+        let check = |value: u128| match value > max {
+            true => Err(TemplateError::OutOfRange { field, value, max }),
+            false => Ok(()),
+        };
+
+        let next = match variation.variation {
+            Variation::Random { min, max } => {
+                check(max)?;
+                if min > max {
+                    return Err(TemplateError::InvalidRange(field));
+                }
+                min
+            }
+            Variation::Sequence { start, step } => {
+                check(start)?;
+                if step == 0 {
+                    return Err(TemplateError::ZeroStep(field));
+                }
+                start
+            }
+            Variation::Sweep { min, max, step } => {
+                check(max)?;
+                if min > max {
+                    return Err(TemplateError::InvalidRange(field));
+                }
+                if step == 0 {
+                    return Err(TemplateError::ZeroStep(field));
+                }
+                min
+            }
+        };
+
+        Ok(Self {
+            field,
+            variation: variation.variation,
+            max,
+            next,
+        })
+    }
+
+    #[inline]
+    fn next_value(&mut self, rng: &mut Rng) -> u128 {
+        match self.variation {
+            Variation::Random { min, max } => match max - min {
+                u128::MAX => rng.next_u128(),
+                span => min + rng.next_below(span + 1),
+            },
+            Variation::Sequence { step, .. } => {
+                let value = self.next;
+                self.next = match self.max {
+                    u128::MAX => value.wrapping_add(step),
+                    // `value` and `step % (max + 1)` are both at most
+                    // `max`, which is below 2^64 here, so the sum does not
+                    // overflow.
+                    max => (value + step % (max + 1)) % (max + 1),
+                };
+                value
+            }
+            Variation::Sweep { min, max, step } => {
+                let value = self.next;
+                self.next = match value.checked_add(step) {
+                    Some(next) if next <= max => next,
+                    _ => min,
+                };
+                value
+            }
+        }
+    }
+}
+
+/// Largest value of `field`, or `None` when the packet lacks its header.
+fn field_max(field: Field, packet: &PacketBuilder) -> Option<u128> {
+    match field {
+        Field::SourceMac | Field::DestinationMac => Some((1 << 48) - 1),
+        Field::VlanId(tag) => (tag < packet.ethernet.vlan_tags.len()).then_some(0x0fff),
+        Field::SourceIp | Field::DestinationIp => match packet.network.as_ref()? {
+            NetworkHeader::Ipv4(_) => Some(u32::MAX as u128),
+            NetworkHeader::Ipv6(_) => Some(u128::MAX),
+        },
+        Field::SourcePort | Field::DestinationPort => match packet.transport.as_ref()? {
+            TransportHeader::Udp(_) | TransportHeader::Tcp(_) => Some(u16::MAX as u128),
+            TransportHeader::Icmp(_) => None,
+        },
+        Field::PayloadLength => Some(packet.payload.len() as u128),
+    }
+}
+
+/// A computed UDP checksum of zero is sent as all ones, because zero means
+/// that there is no checksum.
+#[inline]
+fn fix_udp_checksum(checksum: u16, is_udp: bool) -> u16 {
+    match (checksum, is_udp) {
+        (0, true) => 0xffff,
+        _ => checksum,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("The template has no header for {0:?}")]
+    MissingHeader(Field),
+    #[error("Value {value} is out of range for {field:?}, which holds at most {max}")]
+    OutOfRange {
+        field: Field,
+        value: u128,
+        max: u128,
+    },
+    #[error("Minimum is greater than maximum for {0:?}")]
+    InvalidRange(Field),
+    #[error("Step of {0:?} must be greater than 0")]
+    ZeroStep(Field),
+    #[error("Failed to seed the random number generator: {0}")]
+    Seed(getrandom::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mangonel_packet::{
+        EtherType, Ipv4Header, Ipv4Packet, Ipv6Header, Ipv6Packet, TcpHeader, TcpSegment,
+        UdpDatagram, UdpHeader,
+    };
+    use std::net::Ipv4Addr;
+
+    fn udp_template() -> PacketBuilder {
+        PacketBuilder {
+            network: Some(NetworkHeader::Ipv4(Ipv4Header {
+                source: Ipv4Addr::new(10, 0, 0, 1),
+                destination: Ipv4Addr::new(10, 0, 0, 2),
+                ..Default::default()
+            })),
+            transport: Some(TransportHeader::Udp(UdpHeader {
+                source_port: 1000,
+                destination_port: 2000,
+            })),
+            payload: (0..100).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Writes the fields the same way from scratch and compares.
+    fn assert_same_as_rebuilt(template: &PacketBuilder, packet: &[u8]) {
+        let mut rebuilt = vec![0; packet.len()];
+        template.write(&mut rebuilt).unwrap();
+        assert_eq!(packet, rebuilt.as_slice());
+    }
+
+    #[test]
+    fn ipv4_udp_fields() {
+        let base = udp_template();
+        let mut template = FlowTemplate::new(
+            base.clone(),
+            &[
+                FieldVariation {
+                    field: Field::SourceIp,
+                    variation: Variation::Sequence {
+                        start: u32::from(Ipv4Addr::new(192, 168, 0, 1)) as u128,
+                        step: 1,
+                    },
+                },
+                FieldVariation {
+                    field: Field::DestinationPort,
+                    variation: Variation::Sweep {
+                        min: 10,
+                        max: 12,
+                        step: 1,
+                    },
+                },
+                FieldVariation {
+                    field: Field::PayloadLength,
+                    variation: Variation::Random { min: 0, max: 100 },
+                },
+            ],
+            Some(1),
+        )
+        .unwrap();
+
+        let mut packet = vec![0; 256];
+        template.write(&mut packet).unwrap();
+        for index in 0..8u32 {
+            let length = template.apply(&mut packet);
+            let ip = Ipv4Packet::new(&packet[14..length]).unwrap();
+            let datagram = UdpDatagram::new(ip.payload()).unwrap();
+            let payload_len = datagram.payload().len();
+
+            let mut expected = base.clone();
+            let Some(NetworkHeader::Ipv4(header)) = &mut expected.network else {
+                unreachable!()
+            };
+            header.source = Ipv4Addr::from(u32::from(Ipv4Addr::new(192, 168, 0, 1)) + index);
+            expected.transport = Some(TransportHeader::Udp(UdpHeader {
+                source_port: 1000,
+                destination_port: 10 + (index % 3) as u16,
+            }));
+            expected.payload.truncate(payload_len);
+            assert_same_as_rebuilt(&expected, &packet[..length]);
+        }
+    }
+
+    #[test]
+    fn ipv6_tcp_fields() {
+        let base = PacketBuilder {
+            network: Some(NetworkHeader::Ipv6(Ipv6Header {
+                source: "fd00::1".parse().unwrap(),
+                destination: "fd00::2".parse().unwrap(),
+                ..Default::default()
+            })),
+            transport: Some(TransportHeader::Tcp(TcpHeader::default())),
+            payload: vec![0xab; 33],
+            ..Default::default()
+        };
+        let mut template = FlowTemplate::new(
+            base.clone(),
+            &[
+                FieldVariation {
+                    field: Field::DestinationIp,
+                    variation: Variation::Random {
+                        min: 0,
+                        max: u128::MAX,
+                    },
+                },
+                FieldVariation {
+                    field: Field::PayloadLength,
+                    variation: Variation::Sweep {
+                        min: 0,
+                        max: 33,
+                        step: 7,
+                    },
+                },
+            ],
+            Some(2),
+        )
+        .unwrap();
+
+        let mut packet = vec![0; 256];
+        template.write(&mut packet).unwrap();
+        for index in 0..10 {
+            let length = template.apply(&mut packet);
+            let ip = Ipv6Packet::new(&packet[14..length]).unwrap();
+            let segment = TcpSegment::new(ip.payload()).unwrap();
+            assert_eq!(segment.payload().len(), (index % 5) * 7);
+
+            let mut expected = base.clone();
+            let Some(NetworkHeader::Ipv6(header)) = &mut expected.network else {
+                unreachable!()
+            };
+            header.destination = ip.destination();
+            expected.payload.truncate(segment.payload().len());
+            assert_same_as_rebuilt(&expected, &packet[..length]);
+        }
+    }
+
+    #[test]
+    fn link_layer_fields() {
+        let mut base = udp_template();
+        base.ethernet.vlan_tags.push(VlanTag {
+            tpid: EtherType::VLAN,
+            tci: 0xa000,
+        });
+        let mut template = FlowTemplate::new(
+            base,
+            &[
+                FieldVariation {
+                    field: Field::SourceMac,
+                    variation: Variation::Sequence {
+                        start: (1 << 48) - 1,
+                        step: 1,
+                    },
+                },
+                FieldVariation {
+                    field: Field::VlanId(0),
+                    variation: Variation::Sequence {
+                        start: 4095,
+                        step: 2,
+                    },
+                },
+            ],
+            Some(3),
+        )
+        .unwrap();
+
+        let mut packet = vec![0; 256];
+        template.write(&mut packet).unwrap();
+        template.apply(&mut packet);
+        let frame = EthernetFrame::new(&packet).unwrap();
+        assert_eq!(frame.source(), [0xff; 6]);
+        assert_eq!(frame.vlan_tags().next().unwrap().vlan_id(), 4095);
+
+        template.apply(&mut packet);
+        let frame = EthernetFrame::new(&packet).unwrap();
+        assert_eq!(frame.source(), [0; 6]);
+        let tag = frame.vlan_tags().next().unwrap();
+        assert_eq!(tag.vlan_id(), 1);
+        assert_eq!(tag.pcp(), 5);
+    }
+
+    #[test]
+    fn deterministic_seed() {
+        let variations = [FieldVariation {
+            field: Field::SourcePort,
+            variation: Variation::Random {
+                min: 1024,
+                max: 65535,
+            },
+        }];
+        let mut a = FlowTemplate::new(udp_template(), &variations, Some(42)).unwrap();
+        let mut b = FlowTemplate::new(udp_template(), &variations, Some(42)).unwrap();
+
+        let mut packet_a = vec![0; 256];
+        let mut packet_b = vec![0; 256];
+        a.write(&mut packet_a).unwrap();
+        b.write(&mut packet_b).unwrap();
+        for _ in 0..16 {
+            a.apply(&mut packet_a);
+            b.apply(&mut packet_b);
+            assert_eq!(packet_a, packet_b);
+            assert!(read_u16(&packet_a, 34) >= 1024);
+        }
+    }
+
+    #[test]
+    fn invalid_variations() {
+        let invalid = |field, variation| {
+            FlowTemplate::new(
+                udp_template(),
+                &[FieldVariation { field, variation }],
+                Some(0),
+            )
+            .unwrap_err()
+        };
+        assert!(matches!(
+            invalid(Field::VlanId(0), Variation::Sequence { start: 0, step: 1 }),
+            TemplateError::MissingHeader(Field::VlanId(0))
+        ));
+        assert!(matches!(
+            invalid(Field::PayloadLength, Variation::Random { min: 0, max: 101 }),
+            TemplateError::OutOfRange { max: 100, .. }
+        ));
+        assert!(matches!(
+            invalid(
+                Field::SourcePort,
+                Variation::Sweep {
+                    min: 2,
+                    max: 1,
+                    step: 1
+                }
+            ),
+            TemplateError::InvalidRange(Field::SourcePort)
+        ));
+        assert!(matches!(
+            invalid(Field::SourcePort, Variation::Sequence { start: 0, step: 0 }),
+            TemplateError::ZeroStep(Field::SourcePort)
+        ));
+    }
+}